@@ -4,6 +4,31 @@ pub use sea_orm_migration::prelude::*;
 mod m20220101_000001_user_table_create;
 mod m20220101_000001_visitors_table_create;
 mod m20240528_022228_projects;
+mod m20240601_000001_hash_user_passwords;
+mod m20240602_000001_jwt_keys_table_create;
+mod m20240603_000001_refresh_token_table_create;
+mod m20240604_000001_user_role;
+mod m20240605_000001_invite_table_create;
+mod m20240606_000001_login_attempt_table_create;
+mod m20240607_000001_user_totp;
+mod m20240608_000001_password_reset_table_create;
+mod m20240609_000001_user_disabled;
+mod m20240610_000001_visitor_details;
+mod m20240611_000001_visitor_scope;
+mod m20240612_000001_visitor_limits;
+mod m20240613_000001_visitor_passcode;
+mod m20240614_000001_visitor_event_table_create;
+mod m20240615_000001_visitor_soft_delete;
+mod m20240616_000001_visitor_timestamptz;
+mod m20240617_000001_visitor_landing;
+mod m20240618_000001_project_search;
+mod m20240619_000001_project_location_index;
+mod m20240620_000001_tag_table_create;
+mod m20240621_000001_spent_refresh_token_table_create;
+mod m20240622_000001_admin_timestamptz;
+mod m20240623_000001_jwt_key_purpose;
+mod m20240624_000001_lowercase_emails;
+mod m20240625_000001_user_email_unique;
 
 pub struct Migrator;
 
@@ -14,6 +39,31 @@ impl MigratorTrait for Migrator {
             Box::new(m20220101_000001_user_table_create::Migration),
             Box::new(m20220101_000001_visitors_table_create::Migration),
             Box::new(m20240528_022228_projects::Migration),
+            Box::new(m20240601_000001_hash_user_passwords::Migration),
+            Box::new(m20240602_000001_jwt_keys_table_create::Migration),
+            Box::new(m20240603_000001_refresh_token_table_create::Migration),
+            Box::new(m20240604_000001_user_role::Migration),
+            Box::new(m20240605_000001_invite_table_create::Migration),
+            Box::new(m20240606_000001_login_attempt_table_create::Migration),
+            Box::new(m20240607_000001_user_totp::Migration),
+            Box::new(m20240608_000001_password_reset_table_create::Migration),
+            Box::new(m20240609_000001_user_disabled::Migration),
+            Box::new(m20240610_000001_visitor_details::Migration),
+            Box::new(m20240611_000001_visitor_scope::Migration),
+            Box::new(m20240612_000001_visitor_limits::Migration),
+            Box::new(m20240613_000001_visitor_passcode::Migration),
+            Box::new(m20240614_000001_visitor_event_table_create::Migration),
+            Box::new(m20240615_000001_visitor_soft_delete::Migration),
+            Box::new(m20240616_000001_visitor_timestamptz::Migration),
+            Box::new(m20240617_000001_visitor_landing::Migration),
+            Box::new(m20240618_000001_project_search::Migration),
+            Box::new(m20240619_000001_project_location_index::Migration),
+            Box::new(m20240620_000001_tag_table_create::Migration),
+            Box::new(m20240621_000001_spent_refresh_token_table_create::Migration),
+            Box::new(m20240622_000001_admin_timestamptz::Migration),
+            Box::new(m20240623_000001_jwt_key_purpose::Migration),
+            Box::new(m20240624_000001_lowercase_emails::Migration),
+            Box::new(m20240625_000001_user_email_unique::Migration),
         ]
     }
 }
@@ -0,0 +1,60 @@
+use sea_orm_migration::{prelude::*, sea_orm::ConnectionTrait};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/* bcrypt hashes always look like `$2b$<cost>$<53 chars>` */
+fn is_hashed(password: &str) -> bool {
+    let bytes = password.as_bytes();
+    bytes.len() == 60
+        && bytes.starts_with(b"$2")
+        && matches!(bytes[2], b'a' | b'b' | b'x' | b'y')
+        && bytes[3] == b'$'
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        let backend = db.get_database_backend();
+
+        let select = Query::select()
+            .columns([User::Id, User::Password])
+            .from(User::Table)
+            .to_owned();
+
+        for row in db.query_all(backend.build(&select)).await? {
+            let id: i32 = row.try_get("", "id")?;
+            let password: String = row.try_get("", "password")?;
+
+            if is_hashed(&password) {
+                continue;
+            }
+
+            let hashed = bcrypt::hash(password, bcrypt::DEFAULT_COST)
+                .map_err(|e| DbErr::Migration(format!("Failed to hash password: {e}")))?;
+
+            let update = Query::update()
+                .table(User::Table)
+                .value(User::Password, hashed)
+                .and_where(Expr::col(User::Id).eq(id))
+                .to_owned();
+
+            db.execute(backend.build(&update)).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        /* hashing is one way, there is nothing to restore */
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+    Password,
+}
@@ -0,0 +1,50 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(JwtKey::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(JwtKey::Kid)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(JwtKey::Secret)
+                            .string()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(JwtKey::CreatedAt)
+                            .date_time()
+                            .not_null()
+                    )
+                    .col(ColumnDef::new(JwtKey::RetiredAt).date_time())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(JwtKey::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum JwtKey {
+    Table,
+    Kid,
+    Secret,
+    CreatedAt,
+    RetiredAt,
+}
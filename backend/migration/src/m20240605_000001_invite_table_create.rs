@@ -0,0 +1,94 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Invite::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Invite::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(Invite::TokenHash)
+                            .string()
+                            .not_null()
+                            .unique_key()
+                    )
+                    .col(
+                        ColumnDef::new(Invite::Email)
+                            .string()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Invite::Role)
+                            .string()
+                            .not_null()
+                    )
+                    .col(ColumnDef::new(Invite::CreatedBy).integer())
+                    .col(
+                        ColumnDef::new(Invite::CreatedAt)
+                            .date_time()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Invite::ExpiresAt)
+                            .date_time()
+                            .not_null()
+                    )
+                    .col(ColumnDef::new(Invite::UsedAt).date_time())
+                    .col(ColumnDef::new(Invite::UsedBy).integer())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-invite-created_by")
+                            .from(Invite::Table, Invite::CreatedBy)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::SetNull)
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-invite-used_by")
+                            .from(Invite::Table, Invite::UsedBy)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::SetNull)
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Invite::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Invite {
+    Table,
+    Id,
+    TokenHash,
+    Email,
+    Role,
+    CreatedBy,
+    CreatedAt,
+    ExpiresAt,
+    UsedAt,
+    UsedBy,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
@@ -0,0 +1,77 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(PasswordReset::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PasswordReset::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(PasswordReset::UserId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(PasswordReset::TokenHash)
+                            .string()
+                            .not_null()
+                            .unique_key()
+                    )
+                    .col(
+                        ColumnDef::new(PasswordReset::CreatedAt)
+                            .date_time()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(PasswordReset::ExpiresAt)
+                            .date_time()
+                            .not_null()
+                    )
+                    .col(ColumnDef::new(PasswordReset::UsedAt).date_time())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-password_reset-user_id")
+                            .from(PasswordReset::Table, PasswordReset::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PasswordReset::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum PasswordReset {
+    Table,
+    Id,
+    UserId,
+    TokenHash,
+    CreatedAt,
+    ExpiresAt,
+    UsedAt,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
@@ -0,0 +1,92 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Visitor::Table)
+                    .add_column(ColumnDef::new(Visitor::Label).string())
+                    .add_column(ColumnDef::new(Visitor::ClientName).string())
+                    .add_column(ColumnDef::new(Visitor::ClientCompany).string())
+                    .add_column(ColumnDef::new(Visitor::Notes).text())
+                    .add_column(ColumnDef::new(Visitor::CreatedBy).integer())
+                    .add_column(
+                        ColumnDef::new(Visitor::CreatedAt)
+                            .date_time()
+                            .not_null()
+                            .default(Expr::current_timestamp())
+                    )
+                    .add_column(ColumnDef::new(Visitor::LastSeenAt).date_time())
+                    .add_column(
+                        ColumnDef::new(Visitor::VisitCount)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk-visitor-created_by")
+                    .from(Visitor::Table, Visitor::CreatedBy)
+                    .to(User::Table, User::Id)
+                    .on_delete(ForeignKeyAction::SetNull)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_foreign_key(
+                ForeignKey::drop()
+                    .name("fk-visitor-created_by")
+                    .table(Visitor::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Visitor::Table)
+                    .drop_column(Visitor::Label)
+                    .drop_column(Visitor::ClientName)
+                    .drop_column(Visitor::ClientCompany)
+                    .drop_column(Visitor::Notes)
+                    .drop_column(Visitor::CreatedBy)
+                    .drop_column(Visitor::CreatedAt)
+                    .drop_column(Visitor::LastSeenAt)
+                    .drop_column(Visitor::VisitCount)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Visitor {
+    Table,
+    Label,
+    ClientName,
+    ClientCompany,
+    Notes,
+    CreatedBy,
+    CreatedAt,
+    LastSeenAt,
+    VisitCount,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
@@ -0,0 +1,42 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        /* null means no restriction, every set scope has to match */
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Visitor::Table)
+                    .add_column(ColumnDef::new(Visitor::ScopeProjectIds).array(ColumnType::Integer))
+                    .add_column(ColumnDef::new(Visitor::ScopeCountry).text())
+                    .add_column(ColumnDef::new(Visitor::ScopeYear).integer())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Visitor::Table)
+                    .drop_column(Visitor::ScopeProjectIds)
+                    .drop_column(Visitor::ScopeCountry)
+                    .drop_column(Visitor::ScopeYear)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Visitor {
+    Table,
+    ScopeProjectIds,
+    ScopeCountry,
+    ScopeYear,
+}
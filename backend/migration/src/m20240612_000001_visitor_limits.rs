@@ -0,0 +1,60 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Visitor::Table)
+                    .add_column(ColumnDef::new(Visitor::MaxPageLoads).integer())
+                    .add_column(
+                        ColumnDef::new(Visitor::PageLoads)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .add_column(ColumnDef::new(Visitor::MaxDevices).integer())
+                    .add_column(
+                        ColumnDef::new(Visitor::DeviceIds)
+                            .array(ColumnType::Text)
+                            .not_null()
+                            .default(Expr::cust("'{}'"))
+                    )
+                    .add_column(ColumnDef::new(Visitor::ActivateForSec).big_integer())
+                    .add_column(ColumnDef::new(Visitor::ActivatedAt).date_time())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Visitor::Table)
+                    .drop_column(Visitor::MaxPageLoads)
+                    .drop_column(Visitor::PageLoads)
+                    .drop_column(Visitor::MaxDevices)
+                    .drop_column(Visitor::DeviceIds)
+                    .drop_column(Visitor::ActivateForSec)
+                    .drop_column(Visitor::ActivatedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Visitor {
+    Table,
+    MaxPageLoads,
+    PageLoads,
+    MaxDevices,
+    DeviceIds,
+    ActivateForSec,
+    ActivatedAt,
+}
@@ -0,0 +1,87 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(VisitorEvent::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(VisitorEvent::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(VisitorEvent::VisitorUuid)
+                            .string()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(VisitorEvent::Kind)
+                            .string()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(VisitorEvent::Path)
+                            .text()
+                            .not_null()
+                    )
+                    .col(ColumnDef::new(VisitorEvent::Query).text())
+                    .col(
+                        ColumnDef::new(VisitorEvent::CreatedAt)
+                            .date_time()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-visitor_event-visitor_uuid")
+                            .from(VisitorEvent::Table, VisitorEvent::VisitorUuid)
+                            .to(Visitor::Table, Visitor::Uuid)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-visitor_event-visitor_uuid-created_at")
+                    .table(VisitorEvent::Table)
+                    .col(VisitorEvent::VisitorUuid)
+                    .col(VisitorEvent::CreatedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(VisitorEvent::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum VisitorEvent {
+    Table,
+    Id,
+    VisitorUuid,
+    Kind,
+    Path,
+    Query,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Visitor {
+    Table,
+    Uuid,
+}
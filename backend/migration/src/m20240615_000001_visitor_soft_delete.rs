@@ -0,0 +1,65 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Visitor::Table)
+                    .add_column(ColumnDef::new(Visitor::DeletedAt).date_time())
+                    .to_owned(),
+            )
+            .await?;
+
+        /* the sweeper looks rows up by both */
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-visitor-time_out")
+                    .table(Visitor::Table)
+                    .col(Visitor::TimeOut)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-visitor-deleted_at")
+                    .table(Visitor::Table)
+                    .col(Visitor::DeletedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(Index::drop().name("idx-visitor-deleted_at").to_owned())
+            .await?;
+
+        manager
+            .drop_index(Index::drop().name("idx-visitor-time_out").to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Visitor::Table)
+                    .drop_column(Visitor::DeletedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Visitor {
+    Table,
+    TimeOut,
+    DeletedAt,
+}
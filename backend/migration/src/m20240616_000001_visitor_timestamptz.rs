@@ -0,0 +1,58 @@
+use sea_orm_migration::prelude::*;
+use std::env;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const COLUMNS: [(&str, &str); 6] = [
+    ("visitor", "time_out"),
+    ("visitor", "created_at"),
+    ("visitor", "last_seen_at"),
+    ("visitor", "activated_at"),
+    ("visitor", "deleted_at"),
+    ("visitor_event", "created_at"),
+];
+
+/// Existing values were written in the server's local time. `LEGACY_TIME_ZONE`
+/// (e.g. `Europe/Warsaw`) names that zone, the database's own zone is
+/// assumed otherwise.
+fn legacy_zone() -> Result<String, DbErr> {
+    match env::var("LEGACY_TIME_ZONE") {
+        Ok(zone) if zone.chars().all(|c| c.is_ascii_alphanumeric() || "_/+-".contains(c)) => {
+            Ok(format!("'{zone}'"))
+        }
+        Ok(zone) => Err(DbErr::Migration(format!("Invalid LEGACY_TIME_ZONE: {zone}"))),
+        Err(_) => Ok("current_setting('TimeZone')".to_owned()),
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let zone = legacy_zone()?;
+        let db = manager.get_connection();
+
+        for (table, column) in COLUMNS {
+            db.execute_unprepared(&format!(
+                r#"ALTER TABLE "{table}" ALTER COLUMN "{column}" TYPE timestamptz USING "{column}" AT TIME ZONE {zone}"#
+            ))
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let zone = legacy_zone()?;
+        let db = manager.get_connection();
+
+        for (table, column) in COLUMNS {
+            db.execute_unprepared(&format!(
+                r#"ALTER TABLE "{table}" ALTER COLUMN "{column}" TYPE timestamp USING "{column}" AT TIME ZONE {zone}"#
+            ))
+            .await?;
+        }
+
+        Ok(())
+    }
+}
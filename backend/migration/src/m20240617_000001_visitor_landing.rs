@@ -0,0 +1,67 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Visitor::Table)
+                    .add_column(ColumnDef::new(Visitor::LandingProjectId).integer())
+                    .add_column(ColumnDef::new(Visitor::DefaultCountry).text())
+                    .add_column(ColumnDef::new(Visitor::DefaultYear).integer())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk-visitor-landing_project_id")
+                    .from(Visitor::Table, Visitor::LandingProjectId)
+                    .to(Projects::Table, Projects::Id)
+                    .on_delete(ForeignKeyAction::SetNull)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_foreign_key(
+                ForeignKey::drop()
+                    .name("fk-visitor-landing_project_id")
+                    .table(Visitor::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Visitor::Table)
+                    .drop_column(Visitor::LandingProjectId)
+                    .drop_column(Visitor::DefaultCountry)
+                    .drop_column(Visitor::DefaultYear)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Visitor {
+    Table,
+    LandingProjectId,
+    DefaultCountry,
+    DefaultYear,
+}
+
+#[derive(DeriveIden)]
+enum Projects {
+    Table,
+    Id,
+}
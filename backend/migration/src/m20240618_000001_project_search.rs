@@ -0,0 +1,55 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/* `simple` keeps place and client names as typed instead of stemming them
+   as english, must match the config the backend builds its queries with */
+const SEARCH_COLUMN: &str = r#"ALTER TABLE "projects" ADD COLUMN "search" tsvector
+    GENERATED ALWAYS AS (
+        setweight(to_tsvector('simple', coalesce("name", '')), 'A')
+        || setweight(to_tsvector('simple', coalesce("country", '')), 'B')
+        || setweight(to_tsvector('simple', coalesce("description", '')), 'C')
+    ) STORED"#;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared(SEARCH_COLUMN)
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-projects-search")
+                    .table(Projects::Table)
+                    .col(Projects::Search)
+                    .full_text()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(Index::drop().name("idx-projects-search").to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Projects::Table)
+                    .drop_column(Projects::Search)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Projects {
+    Table,
+    Search,
+}
@@ -0,0 +1,34 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        /* radius and map view queries narrow projects down by a box first */
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-projects-location")
+                    .table(Projects::Table)
+                    .col(Projects::Latitude)
+                    .col(Projects::Longitude)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(Index::drop().name("idx-projects-location").to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Projects {
+    Table,
+    Latitude,
+    Longitude,
+}
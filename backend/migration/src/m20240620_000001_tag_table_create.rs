@@ -0,0 +1,116 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Tag::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Tag::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(Tag::Slug)
+                            .text()
+                            .not_null()
+                            .unique_key()
+                    )
+                    .col(
+                        ColumnDef::new(Tag::Name)
+                            .text()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ProjectTag::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ProjectTag::ProjectId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ProjectTag::TagId)
+                            .integer()
+                            .not_null()
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(ProjectTag::ProjectId)
+                            .col(ProjectTag::TagId)
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-project_tag-project_id")
+                            .from(ProjectTag::Table, ProjectTag::ProjectId)
+                            .to(Projects::Table, Projects::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-project_tag-tag_id")
+                            .from(ProjectTag::Table, ProjectTag::TagId)
+                            .to(Tag::Table, Tag::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        /* the primary key covers lookups by project, this one by tag */
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-project_tag-tag_id")
+                    .table(ProjectTag::Table)
+                    .col(ProjectTag::TagId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ProjectTag::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(Tag::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Tag {
+    Table,
+    Id,
+    Slug,
+    Name,
+}
+
+#[derive(DeriveIden)]
+enum ProjectTag {
+    Table,
+    ProjectId,
+    TagId,
+}
+
+#[derive(DeriveIden)]
+enum Projects {
+    Table,
+    Id,
+}
@@ -0,0 +1,63 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        /* refresh tokens that were already rotated, presenting one again
+           means it leaked and the whole session is revoked */
+        manager
+            .create_table(
+                Table::create()
+                    .table(SpentRefreshToken::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(SpentRefreshToken::TokenHash)
+                            .string()
+                            .not_null()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(SpentRefreshToken::SessionId)
+                            .string()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(SpentRefreshToken::CreatedAt)
+                            .date_time()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-spent_refresh_token-session_id")
+                            .from(SpentRefreshToken::Table, SpentRefreshToken::SessionId)
+                            .to(RefreshToken::Table, RefreshToken::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SpentRefreshToken::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum SpentRefreshToken {
+    Table,
+    TokenHash,
+    SessionId,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum RefreshToken {
+    Table,
+    Id,
+}
@@ -0,0 +1,51 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const COLUMNS: [(&str, &str); 14] = [
+    ("jwt_key", "created_at"),
+    ("jwt_key", "retired_at"),
+    ("refresh_token", "created_at"),
+    ("refresh_token", "expires_at"),
+    ("refresh_token", "revoked_at"),
+    ("spent_refresh_token", "created_at"),
+    ("invite", "created_at"),
+    ("invite", "expires_at"),
+    ("invite", "used_at"),
+    ("login_attempt", "last_failure"),
+    ("login_attempt", "blocked_until"),
+    ("password_reset", "created_at"),
+    ("password_reset", "expires_at"),
+    ("password_reset", "used_at"),
+];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        /* unlike the visitor tables, these were always written in UTC */
+        for (table, column) in COLUMNS {
+            db.execute_unprepared(&format!(
+                r#"ALTER TABLE "{table}" ALTER COLUMN "{column}" TYPE timestamptz USING "{column}" AT TIME ZONE 'UTC'"#
+            ))
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        for (table, column) in COLUMNS {
+            db.execute_unprepared(&format!(
+                r#"ALTER TABLE "{table}" ALTER COLUMN "{column}" TYPE timestamp USING "{column}" AT TIME ZONE 'UTC'"#
+            ))
+            .await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,46 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        /* existing keys sign admin tokens, visitor sessions get their own */
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(JwtKey::Table)
+                    .add_column(
+                        ColumnDef::new(JwtKey::Purpose)
+                            .string()
+                            .not_null()
+                            .default("admin")
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared(r#"DELETE FROM "jwt_key" WHERE "purpose" <> 'admin'"#)
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(JwtKey::Table)
+                    .drop_column(JwtKey::Purpose)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum JwtKey {
+    Table,
+    Purpose,
+}
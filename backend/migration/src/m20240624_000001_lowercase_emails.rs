@@ -0,0 +1,24 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        /* same as `admin::email::normalize`, logins compare against this form */
+        db.execute_unprepared(r#"UPDATE "user" SET "email" = lower(btrim("email"))"#)
+            .await?;
+        db.execute_unprepared(r#"UPDATE "invite" SET "email" = lower(btrim("email"))"#)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        /* the original casing is gone, lowercase emails work either way */
+        Ok(())
+    }
+}
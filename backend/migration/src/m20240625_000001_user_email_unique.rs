@@ -0,0 +1,45 @@
+use sea_orm_migration::{prelude::*, sea_orm::Statement};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        /* accounts differing only in case have to be merged by hand first */
+        let duplicates = db
+            .query_all(Statement::from_string(
+                manager.get_database_backend(),
+                r#"SELECT lower("email") AS "email" FROM "user" GROUP BY lower("email") HAVING count(*) > 1"#,
+            ))
+            .await?
+            .into_iter()
+            .map(|row| row.try_get::<String>("", "email"))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if !duplicates.is_empty() {
+            return Err(DbErr::Migration(format!(
+                "Several admins share an email: {}",
+                duplicates.join(", ")
+            )));
+        }
+
+        db.execute_unprepared(r#"CREATE UNIQUE INDEX "idx-user-email-lower" ON "user" (lower("email"))"#)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(Index::drop().name("idx-user-email-lower").table(User::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+}
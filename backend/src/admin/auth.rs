@@ -1,23 +1,39 @@
 use crate::{
     entities::user,
-    state::{self, AppState},
+    state::AppState,
 };
 use axum::{
-    body::Body, extract::State, http::{header, Response, StatusCode}, response::IntoResponse, Json
+    extract::{ConnectInfo, State}, http::{header, StatusCode}, response::{IntoResponse, Response}, Json
 };
-use chrono::Utc;
-use jsonwebtoken::{encode, EncodingKey, Header};
-use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter};
+use chrono::{TimeDelta, Utc};
+use jsonwebtoken::{encode, Header};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
 use serde::Deserialize;
-use super::JwtClaims;
+use serde_json::json;
+use std::net::SocketAddr;
+use super::{email, keys::KeyStore, password, session, throttle::Key, totp, JwtClaims};
 
-const VALID_FOR: chrono::TimeDelta = chrono::TimeDelta::seconds(60*60*24); /* 1 day */
+/* sessions are extended through `/admin/refresh` */
+pub(crate) const ACCESS_VALID_FOR: chrono::TimeDelta = chrono::TimeDelta::minutes(15);
 
-pub fn issue_jwt() -> Result<String, jsonwebtoken::errors::Error> {
+pub fn issue_jwt(
+    keys: &KeyStore,
+    user: &user::Model,
+    session_id: &str,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let (kid, key) = keys.encoding_key();
+    let now = Utc::now();
     encode(
-        &Header::default(),
-        &JwtClaims { exp: (Utc::now() + VALID_FOR).timestamp() as usize },
-        &EncodingKey::from_secret(state::SECRET_KEY.as_bytes()),
+        &Header { kid: Some(kid), ..Default::default() },
+        &JwtClaims {
+            exp: (now + ACCESS_VALID_FOR).timestamp() as usize,
+            iat: now.timestamp() as usize,
+            sub: user.id,
+            email: user.email.clone(),
+            role: user.role,
+            sid: session_id.to_owned(),
+        },
+        &key,
     )
 }
 
@@ -27,36 +43,92 @@ pub struct AuthReq {
     password: String,
 }
 
+/// 401, with `Retry-After` when the caller is being throttled
+fn unauthorized(retry_after: Option<TimeDelta>) -> Response {
+    match retry_after {
+        None => (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({ "error": "Invalid credentials" })),
+        )
+            .into_response(),
+        Some(wait) => {
+            /* round up, `Retry-After: 0` would invite an immediate retry */
+            let secs = (wait.num_milliseconds() + 999) / 1000;
+            (
+                StatusCode::UNAUTHORIZED,
+                [(header::RETRY_AFTER, secs.to_string())],
+                Json(json!({ "error": "Too many failed attempts", "retry_after": secs })),
+            )
+                .into_response()
+        }
+    }
+}
+
 pub async fn auth(
     State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     Json(body): Json<AuthReq>, 
 ) -> impl IntoResponse {
-    let count = user::Entity::find()
-        .filter(user::Column::Email.eq(&body.email))
-        .filter(user::Column::Password.eq(&body.password))
-        .count(&state.db_conn)
-        .await;
+    let email = email::normalize(&body.email);
+    let keys = [Key::Ip(addr.ip()), Key::Email(email.clone())];
+
+    match state.throttle.retry_after(&keys).await {
+        Ok(None) => {},
+        Ok(wait) => return unauthorized(wait),
+        Err(e) => {
+            tracing::error!("Failed to read login attempts: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
+    let user = match user::Entity::find()
+        .filter(user::Column::Email.eq(&email))
+        .one(&state.db_conn)
+        .await
+    {
+        Ok(user) => user,
+        Err(_e) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
 
-    match count {
-        Err(_e) => {
+    let hash = user.as_ref().map(|u| u.password.clone());
+
+    match password::verify(body.password, hash).await {
+        Err(e) => {
+            tracing::error!("Password verification failed: {e}");
             StatusCode::INTERNAL_SERVER_ERROR.into_response()
         },
-        Ok(count) => {
-            if count != 1 {
-                StatusCode::IM_A_TEAPOT /* hell yeah I am */
-                    .into_response()
-            } else {
-                match issue_jwt() {
-                    Err(_) => {
-                        StatusCode::INTERNAL_SERVER_ERROR.into_response()
-                    },
-                    Ok(token) => {
-                        Response::builder()
-                            .status(StatusCode::OK)
-                            .header(header::AUTHORIZATION, format!("Bearer {}", token))
-                            .body(Body::default())
-                            .unwrap()
-                            
+        Ok(is_valid) => {
+            match user {
+                /* only told after the right password, not to leak who is disabled */
+                Some(user) if is_valid && user.disabled => {
+                    tracing::warn!("Disabled admin {} tried to log in", user.email);
+                    (
+                        StatusCode::FORBIDDEN,
+                        Json(json!({ "error": "Account is disabled" })),
+                    )
+                        .into_response()
+                },
+                Some(user) if is_valid => {
+                    if user.totp_enabled {
+                        /* the session is only started by `/admin/auth/totp`, which
+                           also resets the counter, so the code can't be guessed
+                           without back-off by logging in again in between */
+                        return totp::challenge(&state, &user).into_response();
+                    }
+                    let [_, email] = &keys;
+                    if let Err(e) = state.throttle.succeeded(email).await {
+                        tracing::error!("Failed to reset login attempts: {e}");
+                    }
+                    session::start(&state, &user).await.into_response()
+                },
+                _ => {
+                    tracing::warn!("Failed login for {} from {}", email, addr.ip());
+                    match state.throttle.failed(&keys).await {
+                        Ok(wait) => unauthorized(wait),
+                        Err(e) => {
+                            tracing::error!("Failed to record login attempt: {e}");
+                            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+                        }
                     }
                 }
             }
@@ -0,0 +1,5 @@
+/// Emails are stored and looked up in this form, so `Admin@Example.com`
+/// and `admin@example.com` are the same account
+pub fn normalize(email: &str) -> String {
+    email.trim().to_lowercase()
+}
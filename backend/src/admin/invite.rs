@@ -0,0 +1,206 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, PaginatorTrait,
+    QueryFilter, QueryOrder, Set,
+};
+use serde::{Deserialize, Serialize};
+use std::{env, path::PathBuf};
+use crate::{
+    entities::{invite, sea_orm_active_enums::Role, user},
+    state::AppState,
+};
+use super::{email, token, verify::AdminIdentity};
+
+const DEFAULT_VALID_FOR: TimeDelta = TimeDelta::days(7);
+
+#[derive(thiserror::Error, Debug)]
+pub enum InviteError {
+    #[error("No pending invite id({0}) found")]
+    NotFound(i32),
+
+    #[error("valid_for_sec is out of range")]
+    InvalidValidity,
+
+    #[error("Db error: {0}")]
+    DbError(#[from] DbErr),
+}
+
+impl IntoResponse for InviteError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::NotFound(_) => (StatusCode::NOT_FOUND, format!("Error: {}", self)).into_response(),
+            Self::InvalidValidity => (StatusCode::BAD_REQUEST, format!("Error: {}", self)).into_response(),
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("There was a problem: {}", self),
+            )
+                .into_response(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CreateInviteReq {
+    pub email: String,
+    #[serde(default = "default_role")]
+    pub role: Role,
+    pub valid_for_sec: Option<u64>,
+}
+
+fn default_role() -> Role {
+    Role::Editor
+}
+
+#[derive(Serialize)]
+pub struct CreateInviteResponse {
+    pub id: i32,
+    /* only ever shown here, the database keeps a digest */
+    pub token: String,
+    pub email: String,
+    pub role: Role,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Where the bootstrap invite token is written, `BOOTSTRAP_TOKEN_FILE` or
+/// `bootstrap_invite_token` in the working directory
+pub(crate) fn bootstrap_token_file() -> PathBuf {
+    env::var("BOOTSTRAP_TOKEN_FILE")
+        .unwrap_or_else(|_| "bootstrap_invite_token".into())
+        .into()
+}
+
+/// On a fresh database nobody could create the first invite, so an owner
+/// invite is issued on startup and its token returned, to be written to
+/// [`bootstrap_token_file`]. While that invite is pending restarts don't
+/// issue another one. A lost token is recovered by deleting the file and
+/// restarting, the pending invite is then replaced.
+pub(crate) async fn bootstrap(db_conn: &DatabaseConnection) -> Result<Option<String>, DbErr> {
+    if user::Entity::find().count(db_conn).await? > 0 {
+        return Ok(None);
+    }
+
+    let now = Utc::now();
+    let pending = invite::Entity::find()
+        .filter(invite::Column::CreatedBy.is_null())
+        .filter(invite::Column::Role.eq(Role::Owner))
+        .filter(invite::Column::UsedAt.is_null())
+        .filter(invite::Column::ExpiresAt.gt(now))
+        .one(db_conn)
+        .await?;
+
+    if let Some(pending) = pending {
+        if bootstrap_token_file().exists() {
+            tracing::warn!(
+                "No admins yet, bootstrap invite id({}) for {} is pending until {}",
+                pending.id,
+                pending.email,
+                pending.expires_at
+            );
+            return Ok(None);
+        }
+
+        tracing::warn!("Bootstrap token file is gone, replacing invite id({})", pending.id);
+        invite::Entity::delete_by_id(pending.id).exec(db_conn).await?;
+    }
+
+    let token = token::random();
+    invite::ActiveModel {
+        id: sea_orm::NotSet,
+        token_hash: Set(token::digest(&token)),
+        email: Set(email::normalize(
+            &env::var("ADMIN_EMAIL").unwrap_or_else(|_| "admin@localhost".into()),
+        )),
+        role: Set(Role::Owner),
+        created_by: Set(None),
+        created_at: Set(now),
+        expires_at: Set(now + DEFAULT_VALID_FOR),
+        used_at: Set(None),
+        used_by: Set(None),
+    }
+    .insert(db_conn)
+    .await?;
+
+    Ok(Some(token))
+}
+
+pub async fn create(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+    Json(req): Json<CreateInviteReq>,
+) -> Result<(StatusCode, Json<CreateInviteResponse>), InviteError> {
+    let now = Utc::now();
+    let expires_at = match req.valid_for_sec {
+        Some(sec) => i64::try_from(sec)
+            .ok()
+            .and_then(TimeDelta::try_seconds)
+            .and_then(|valid_for| now.checked_add_signed(valid_for))
+            .ok_or(InviteError::InvalidValidity)?,
+        None => now + DEFAULT_VALID_FOR,
+    };
+    let token = token::random();
+
+    let invite = invite::ActiveModel {
+        id: sea_orm::NotSet,
+        token_hash: Set(token::digest(&token)),
+        email: Set(email::normalize(&req.email)),
+        role: Set(req.role),
+        created_by: Set(Some(identity.id)),
+        created_at: Set(now),
+        expires_at: Set(expires_at),
+        used_at: Set(None),
+        used_by: Set(None),
+    }
+    .insert(&state.db_conn)
+    .await?;
+
+    tracing::info!(
+        "{} invited {} as {:?}",
+        identity.email,
+        invite.email,
+        invite.role
+    );
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateInviteResponse {
+            id: invite.id,
+            token,
+            email: invite.email,
+            role: invite.role,
+            expires_at: invite.expires_at,
+        }),
+    ))
+}
+
+pub async fn list(State(state): State<AppState>) -> Result<Json<Vec<invite::Model>>, InviteError> {
+    let invites = invite::Entity::find()
+        .order_by_desc(invite::Column::CreatedAt)
+        .all(&state.db_conn)
+        .await?;
+
+    Ok(Json(invites))
+}
+
+/// Withdraws an invite that has not been redeemed yet
+pub async fn revoke(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, InviteError> {
+    let res = invite::Entity::delete_many()
+        .filter(invite::Column::Id.eq(id))
+        .filter(invite::Column::UsedAt.is_null())
+        .exec(&state.db_conn)
+        .await?;
+
+    if res.rows_affected == 0 {
+        return Err(InviteError::NotFound(id));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
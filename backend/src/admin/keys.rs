@@ -0,0 +1,281 @@
+use crate::{entities::jwt_key, state::AppState};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::Utc;
+use jsonwebtoken::{DecodingKey, EncodingKey};
+use rand::{distributions::Alphanumeric, Rng};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait,
+    DatabaseConnection, DatabaseTransaction, DbBackend, DbErr, EntityTrait, QueryFilter,
+    QueryOrder, Set, Statement, TransactionTrait,
+};
+use serde::Serialize;
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    env,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+use super::auth::ACCESS_VALID_FOR;
+use crate::visitor::MAX_SESSION_FOR;
+
+/* unknown `kid`s trigger a reload, but not more often than this */
+const MIN_RELOAD_INTERVAL: Duration = Duration::from_secs(5);
+/* advisory lock id, serializes seeding and rotating across instances */
+const KEYS_LOCK: i64 = 0x006a_7774_5f6b_6579;
+
+/// What a key signs, stored in `jwt_key.purpose`. Each purpose has its own
+/// active key and is rotated on its own
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum KeyPurpose {
+    #[default]
+    Admin,
+    Visitor,
+}
+
+impl KeyPurpose {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Admin => "admin",
+            Self::Visitor => "visitor",
+        }
+    }
+
+    /// Other instances may keep signing with a retired key until their next
+    /// periodic reload, so keep it around a little longer than a token lives
+    fn keep_retired_for(self) -> chrono::TimeDelta {
+        let lives_for = match self {
+            Self::Admin => ACCESS_VALID_FOR,
+            Self::Visitor => MAX_SESSION_FOR,
+        };
+        lives_for + chrono::TimeDelta::minutes(5)
+    }
+}
+
+#[derive(Default)]
+struct Keys {
+    signing_kid: String,
+    secrets: HashMap<String, String>,
+    loaded_at: Option<Instant>,
+}
+
+/// JWT signing keys shared by every instance through the `jwt_key` table.
+/// The newest non retired key signs new tokens, retired keys keep verifying
+/// until every token they signed has expired.
+#[derive(Clone, Default)]
+pub struct KeyStore {
+    purpose: KeyPurpose,
+    keys: Arc<RwLock<Keys>>,
+}
+
+fn random_secret() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(64)
+        .map(char::from)
+        .collect()
+}
+
+/// Held until `txn` ends
+async fn lock_keys(txn: &DatabaseTransaction) -> Result<(), DbErr> {
+    txn.execute(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        "SELECT pg_advisory_xact_lock($1)",
+        [KEYS_LOCK.into()],
+    ))
+    .await?;
+
+    Ok(())
+}
+
+async fn insert_key<C: ConnectionTrait>(
+    db_conn: &C,
+    purpose: KeyPurpose,
+    secret: String,
+) -> Result<jwt_key::Model, DbErr> {
+    jwt_key::ActiveModel {
+        kid: Set(Uuid::new_v4().to_string()),
+        secret: Set(secret),
+        created_at: Set(Utc::now()),
+        retired_at: Set(None),
+        purpose: Set(purpose.as_str().to_owned()),
+    }
+    .insert(db_conn)
+    .await
+}
+
+impl KeyStore {
+    pub async fn load(db_conn: &DatabaseConnection, purpose: KeyPurpose) -> Result<Self, DbErr> {
+        let store = Self {
+            purpose,
+            keys: Default::default(),
+        };
+        store.reload(db_conn).await?;
+        Ok(store)
+    }
+
+    /// Re-reads keys from the database, seeding the first one if there is no
+    /// active key yet
+    pub async fn reload(&self, db_conn: &DatabaseConnection) -> Result<(), DbErr> {
+        let verify_after = Utc::now() - self.purpose.keep_retired_for();
+        let mut rows = jwt_key::Entity::find()
+            .filter(jwt_key::Column::Purpose.eq(self.purpose.as_str()))
+            .filter(
+                Condition::any()
+                    .add(jwt_key::Column::RetiredAt.is_null())
+                    .add(jwt_key::Column::RetiredAt.gt(verify_after)),
+            )
+            .all(db_conn)
+            .await?;
+
+        let signing = rows
+            .iter()
+            .filter(|k| k.retired_at.is_none())
+            .max_by_key(|k| k.created_at)
+            .map(|k| k.kid.clone());
+
+        let signing_kid = match signing {
+            Some(kid) => kid,
+            None => {
+                let key = self.seed(db_conn).await?;
+                let kid = key.kid.clone();
+                if !rows.iter().any(|k| k.kid == kid) {
+                    rows.push(key);
+                }
+                kid
+            }
+        };
+
+        let mut keys = self.keys.write().unwrap();
+        keys.signing_kid = signing_kid;
+        keys.secrets = rows.into_iter().map(|k| (k.kid, k.secret)).collect();
+        keys.loaded_at = Some(Instant::now());
+
+        Ok(())
+    }
+
+    /// The active key, created unless another instance got there first.
+    /// Admin keys start from `JWT_SECRET` when it is set, others are random
+    async fn seed(&self, db_conn: &DatabaseConnection) -> Result<jwt_key::Model, DbErr> {
+        let txn = db_conn.begin().await?;
+        lock_keys(&txn).await?;
+
+        let active = jwt_key::Entity::find()
+            .filter(jwt_key::Column::Purpose.eq(self.purpose.as_str()))
+            .filter(jwt_key::Column::RetiredAt.is_null())
+            .order_by_desc(jwt_key::Column::CreatedAt)
+            .one(&txn)
+            .await?;
+
+        let key = match active {
+            Some(key) => key,
+            None => {
+                let secret = match self.purpose {
+                    KeyPurpose::Admin => env::var("JWT_SECRET").unwrap_or_else(|_| random_secret()),
+                    KeyPurpose::Visitor => random_secret(),
+                };
+                let key = insert_key(&txn, self.purpose, secret).await?;
+                tracing::info!("Created initial {} JWT signing key {}", self.purpose.as_str(), key.kid);
+                key
+            }
+        };
+
+        txn.commit().await?;
+        Ok(key)
+    }
+
+    /// Retires every active key of this purpose and starts signing with a fresh one,
+    /// returns the new `kid`. Runs as one transaction, so other instances
+    /// never see a moment without an active key
+    pub async fn rotate(&self, db_conn: &DatabaseConnection) -> Result<String, DbErr> {
+        let now = Utc::now();
+
+        let txn = db_conn.begin().await?;
+        lock_keys(&txn).await?;
+
+        jwt_key::Entity::update_many()
+            .col_expr(jwt_key::Column::RetiredAt, Expr::value(now))
+            .filter(jwt_key::Column::Purpose.eq(self.purpose.as_str()))
+            .filter(jwt_key::Column::RetiredAt.is_null())
+            .exec(&txn)
+            .await?;
+
+        /* nothing signed by these can still be valid */
+        jwt_key::Entity::delete_many()
+            .filter(jwt_key::Column::Purpose.eq(self.purpose.as_str()))
+            .filter(jwt_key::Column::RetiredAt.lte(now - self.purpose.keep_retired_for()))
+            .exec(&txn)
+            .await?;
+
+        let key = insert_key(&txn, self.purpose, random_secret()).await?;
+        txn.commit().await?;
+        self.reload(db_conn).await?;
+
+        Ok(key.kid)
+    }
+
+    pub fn encoding_key(&self) -> (String, EncodingKey) {
+        let keys = self.keys.read().unwrap();
+        let secret = &keys.secrets[&keys.signing_kid];
+        (keys.signing_kid.clone(), EncodingKey::from_secret(secret.as_bytes()))
+    }
+
+    /// Looks up a verification key, reloading from the database once when
+    /// the `kid` was issued by another instance after our last reload
+    pub async fn decoding_key(&self, db_conn: &DatabaseConnection, kid: &str) -> Option<DecodingKey> {
+        if let Some(key) = self.cached_decoding_key(kid) {
+            return Some(key);
+        }
+
+        let can_reload = self
+            .keys
+            .read()
+            .unwrap()
+            .loaded_at
+            .map_or(true, |at| at.elapsed() >= MIN_RELOAD_INTERVAL);
+
+        if !can_reload {
+            return None;
+        }
+
+        if let Err(e) = self.reload(db_conn).await {
+            tracing::error!("Failed to reload JWT keys: {e}");
+            return None;
+        }
+
+        self.cached_decoding_key(kid)
+    }
+
+    fn cached_decoding_key(&self, kid: &str) -> Option<DecodingKey> {
+        self.keys
+            .read()
+            .unwrap()
+            .secrets
+            .get(kid)
+            .map(|secret| DecodingKey::from_secret(secret.as_bytes()))
+    }
+}
+
+#[derive(Serialize)]
+pub struct RotateResponse {
+    pub kid: String,
+}
+
+pub async fn rotate(State(state): State<AppState>) -> impl IntoResponse {
+    match state.keys.rotate(&state.db_conn).await {
+        Ok(kid) => {
+            tracing::info!("Rotated JWT signing key, new kid={kid}");
+            (StatusCode::OK, Json(RotateResponse { kid })).into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to rotate JWT keys: {e}");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Failed to rotate keys" })),
+            )
+                .into_response()
+        }
+    }
+}
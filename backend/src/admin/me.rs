@@ -0,0 +1,137 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Extension, Json};
+use sea_orm::{ActiveModelTrait, EntityTrait, Set};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use crate::{
+    entities::{sea_orm_active_enums::Role, user},
+    state::AppState,
+};
+use super::{password, session, throttle::Key, verify::AdminIdentity};
+
+#[derive(Serialize)]
+pub struct Profile {
+    pub id: i32,
+    pub name: Option<String>,
+    pub last_name: Option<String>,
+    pub email: String,
+    pub role: Role,
+    pub totp_enabled: bool,
+    pub disabled: bool,
+}
+
+impl From<user::Model> for Profile {
+    fn from(user: user::Model) -> Self {
+        Self {
+            id: user.id,
+            name: user.name,
+            last_name: user.last_name,
+            email: user.email,
+            role: user.role,
+            totp_enabled: user.totp_enabled,
+            disabled: user.disabled,
+        }
+    }
+}
+
+pub async fn profile(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+) -> impl IntoResponse {
+    match user::Entity::find_by_id(identity.id).one(&state.db_conn).await {
+        Ok(Some(user)) => (StatusCode::OK, Json(Profile::from(user))).into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "User no longer exists" })),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("DataBase Error: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordReq {
+    pub old_password: String,
+    pub new_password: String,
+}
+
+/// Every other session of the user is revoked, the current one stays
+pub async fn change_password(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+    Json(body): Json<ChangePasswordReq>,
+) -> impl IntoResponse {
+    if body.new_password.chars().count() < password::MIN_LEN {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": format!("Password must be at least {} characters long", password::MIN_LEN) })),
+        )
+            .into_response();
+    }
+
+    let user = match user::Entity::find_by_id(identity.id).one(&state.db_conn).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            tracing::error!("DataBase Error: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    /* a stolen access token must not allow guessing the password */
+    let keys = [Key::Email(user.email.clone())];
+    match state.throttle.retry_after(&keys).await {
+        Ok(None) => {},
+        Ok(Some(_)) => return StatusCode::TOO_MANY_REQUESTS.into_response(),
+        Err(e) => {
+            tracing::error!("Failed to read login attempts: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
+    match password::verify(body.old_password, Some(user.password.clone())).await {
+        Ok(true) => {},
+        Ok(false) => {
+            if let Err(e) = state.throttle.failed(&keys).await {
+                tracing::error!("Failed to record login attempt: {e}");
+            }
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(json!({ "error": "Old password is incorrect" })),
+            )
+                .into_response();
+        }
+        Err(e) => {
+            tracing::error!("Password verification failed: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
+    let hash = match password::hash(body.new_password).await {
+        Ok(hash) => hash,
+        Err(e) => {
+            tracing::error!("Failed to hash password: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let mut user: user::ActiveModel = user.into();
+    user.password = Set(hash);
+    if let Err(e) = user.update(&state.db_conn).await {
+        tracing::error!("DataBase Error: {e}");
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    match session::revoke_all(&state.db_conn, identity.id, Some(&identity.session_id)).await {
+        Ok(revoked) => {
+            tracing::info!("{} changed their password, {revoked} other session(s) revoked", identity.email);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to revoke sessions: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
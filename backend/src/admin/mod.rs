@@ -1,28 +1,84 @@
 use axum::{middleware, routing};
 use serde::{Deserialize, Serialize};
 use tower_http::services::ServeDir;
-use crate::{common, state::AppState};
+use crate::{common, entities::sea_orm_active_enums::Role, state::AppState};
 
 mod auth;
+mod email;
+pub(crate) mod invite;
+mod keys;
+mod me;
 mod register;
+mod reset;
+mod session;
+mod tags;
+pub(crate) mod throttle;
+mod token;
+mod totp;
+mod users;
 mod visitor;
 mod verify;
 mod projects;
+pub(crate) mod password;
 
 pub use auth::auth;
+pub use register::new_admin as register;
+pub use reset::{confirm as confirm_password_reset, request as request_password_reset};
+pub use session::{logout, refresh};
+pub use throttle::{AttemptStore, Throttle};
+pub use totp::verify as verify_totp;
+pub use verify::AdminIdentity;
 
 #[derive(Serialize, Deserialize)]
 pub struct JwtClaims {
     exp: usize,
+    iat: usize,
+    /* id of the `user` row */
+    sub: i32,
+    email: String,
+    role: Role,
+    /* id of the `refresh_token` row, checked on every request */
+    sid: String,
 }
 
-pub fn api_router() -> axum::Router<AppState> {
+pub use keys::{KeyPurpose, KeyStore};
+
+pub fn api_router(state: AppState) -> axum::Router<AppState> {
+    let owner_routes = axum::Router::new()
+        .route("/invites", routing::post(invite::create).get(invite::list))
+        .route("/invites/:id", routing::delete(invite::revoke))
+        .route("/keys/rotate", routing::post(keys::rotate))
+        /* deletes are owner-only, like project and file deletes */
+        .route("/visitor/:uuid", routing::delete(visitor::revoke))
+        .route("/tags/:id", routing::delete(tags::delete))
+        .route("/users", routing::get(users::list))
+        .route(
+            "/users/:id",
+            routing::get(users::get).patch(users::update).delete(users::delete),
+        )
+        .route_layer(middleware::from_fn_with_state(Role::Owner, verify::require_role));
+
+    let editor_routes = axum::Router::new()
+        .route("/visitor", routing::post(visitor::create).get(visitor::list))
+        .route("/visitor/:uuid", routing::patch(visitor::update))
+        .route("/visitor/:uuid/activity", routing::get(visitor::activity))
+        .route("/tags", routing::post(tags::create))
+        .route("/tags/:id", routing::patch(tags::update))
+        .route_layer(middleware::from_fn_with_state(Role::Editor, verify::require_role));
+
+    /* everything not guarded explicitly is readable by viewers */
     axum::Router::new()
-        .route("/register-admin", routing::post(register::new_admin))
-        .route("/visitor", routing::post(visitor::create))
-        .nest("/projects", projects::get_router()) /* admin routes */
-        .nest("/projects", common::get_router())
-        .layer(middleware::from_fn(verify::is_admin))
+        .route("/me", routing::get(me::profile))
+        .route("/me/password", routing::post(me::change_password))
+        .route("/me/totp", routing::post(totp::enroll).delete(totp::disable))
+        .route("/me/totp/confirm", routing::post(totp::confirm))
+        .route("/tags", routing::get(tags::list))
+        .merge(owner_routes)
+        .merge(editor_routes)
+        .nest("/projects", projects::get_router()) /* admin routes, guarded per route */
+        .nest("/projects", common::get_router(state.clone()))
+        .merge(common::get_export_router())
+        .layer(middleware::from_fn_with_state(state, verify::is_admin))
 }
 
 pub fn page_router(state: AppState) -> axum::Router<AppState> {
@@ -0,0 +1,70 @@
+use crate::entities::user;
+use lazy_static::lazy_static;
+use sea_orm::{ActiveModelTrait, DatabaseConnection, DbErr, EntityTrait, Set};
+
+lazy_static! {
+    /// Hash used when the requested user does not exist, so a failed lookup
+    /// costs as much time as a failed password check
+    static ref DUMMY_HASH: String = bcrypt::hash("", bcrypt::DEFAULT_COST)
+        .expect("failed to hash dummy password");
+}
+
+/// Enforced whenever an admin picks a new password
+pub const MIN_LEN: usize = 8;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PasswordError {
+    #[error("Hashing error: {0}")]
+    Hash(#[from] bcrypt::BcryptError),
+
+    #[error("Hashing task failed: {0}")]
+    Join(#[from] tokio::task::JoinError),
+
+    #[error("Data base error: {0}")]
+    Db(#[from] DbErr),
+}
+
+/// bcrypt hashes always start with `$2a$`, `$2b$`, `$2x$` or `$2y$`
+pub fn is_hashed(password: &str) -> bool {
+    let bytes = password.as_bytes();
+    bytes.len() == 60
+        && bytes.starts_with(b"$2")
+        && matches!(bytes[2], b'a' | b'b' | b'x' | b'y')
+        && bytes[3] == b'$'
+}
+
+/* bcrypt is deliberately slow, so keep it off the async workers */
+pub async fn hash(password: String) -> Result<String, PasswordError> {
+    Ok(tokio::task::spawn_blocking(move || bcrypt::hash(password, bcrypt::DEFAULT_COST)).await??)
+}
+
+/// Constant-time check of `password` against a stored hash, `None` means there
+/// is no such user, but the work is done anyway
+pub async fn verify(password: String, hash: Option<String>) -> Result<bool, PasswordError> {
+    let found = hash.is_some();
+    let hash = hash.unwrap_or_else(|| DUMMY_HASH.clone());
+    let is_valid = tokio::task::spawn_blocking(move || bcrypt::verify(password, &hash)).await??;
+
+    Ok(found && is_valid)
+}
+
+/// Rehashes every `user` row that still holds a plaintext password,
+/// returns the number of rows updated
+pub async fn rehash_legacy(db_conn: &DatabaseConnection) -> Result<usize, PasswordError> {
+    let legacy = user::Entity::find()
+        .all(db_conn)
+        .await?
+        .into_iter()
+        .filter(|u| !is_hashed(&u.password))
+        .collect::<Vec<_>>();
+
+    let count = legacy.len();
+    for u in legacy {
+        let hashed = hash(u.password.clone()).await?;
+        let mut u: user::ActiveModel = u.into();
+        u.password = Set(hashed);
+        u.update(db_conn).await?;
+    }
+
+    Ok(count)
+}
@@ -1,4 +1,5 @@
 use super::{
+    super::tags,
     pic_info::{GeoData, PicInfo, PicInfoError},
     util,
 };
@@ -11,7 +12,7 @@ use axum::{
 };
 use chrono::Datelike;
 use entities::projects;
-use sea_orm::{ActiveModelTrait, DbErr};
+use sea_orm::{ActiveModelTrait, DbErr, TransactionTrait};
 use serde::{Deserialize, Serialize};
 use state::AppState;
 
@@ -28,17 +29,23 @@ pub enum ProjectError {
     )]
     PicParseError(#[from] PicInfoError),
 
+    #[error("No tag id({0}) found")]
+    UnknownTag(i32),
+
     #[error("Db error: {0}")]
     DbError(#[from] DbErr),
 }
 
 impl IntoResponse for ProjectError {
     fn into_response(self) -> Response {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("There was a problem: {}", self),
-        )
-            .into_response()
+        match self {
+            Self::UnknownTag(_) => (StatusCode::BAD_REQUEST, format!("Error: {}", self)).into_response(),
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("There was a problem: {}", self),
+            )
+                .into_response(),
+        }
     }
 }
 
@@ -62,6 +69,9 @@ pub struct ProjectRequest {
     /* will attempt to infer from a picture, or return an error if unable to do so */
     pub year: Option<i32>,
     pub geo_data: Option<GeoData>,
+    /* ids of `tag` rows */
+    #[serde(default)]
+    pub tags: Vec<i32>,
 }
 
 pub async fn project(
@@ -93,6 +103,12 @@ pub async fn project(
         },
     };
 
+    if let Some(id) = tags::find_unknown(&state.db_conn, &info.tags).await? {
+        return Err(ProjectError::UnknownTag(id));
+    }
+
+    let txn = state.db_conn.begin().await?;
+
     let res = projects::ActiveModel {
         id: sea_orm::NotSet,
         name: sea_orm::Set(info.name),
@@ -104,9 +120,12 @@ pub async fn project(
         latitude: sea_orm::Set(geo_data.latitude),
         longitude: sea_orm::Set(geo_data.longitude),
     }
-    .insert(&state.db_conn)
+    .insert(&txn)
     .await?;
 
+    tags::assign(&txn, res.id, &info.tags).await?;
+    txn.commit().await?;
+
     Ok((
         StatusCode::OK,
         Json(ProjectResponse {
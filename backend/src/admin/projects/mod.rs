@@ -1,6 +1,6 @@
-use axum::routing;
+use axum::{middleware, routing};
 
-use crate::state::AppState;
+use crate::{admin::verify::require_role, entities::sea_orm_active_enums::Role, state::AppState};
 mod create;
 mod delete;
 mod update;
@@ -9,12 +9,18 @@ mod util;
 mod pic_info;
 
 pub fn get_router() -> axum::Router<AppState> {
-    axum::Router::new()
+    let editor_routes = axum::Router::new()
         .route("/", routing::post(create::project)) 
         .route("/:id", routing::patch(update::project)) 
-        .route("/:id", routing::delete(delete::project)) 
         .route("/pictures", routing::post(upload::pictures))
         .route("/videos", routing::post(upload::videos))
+        .route_layer(middleware::from_fn_with_state(Role::Editor, require_role));
+
+    let owner_routes = axum::Router::new()
+        .route("/:id", routing::delete(delete::project)) 
         /* delete is there because of issue, of dynamic route conflicts*/
         .route("/storage/delete/:file_name", routing::delete(delete::file))
+        .route_layer(middleware::from_fn_with_state(Role::Owner, require_role));
+
+    editor_routes.merge(owner_routes)
 }
@@ -1,3 +1,4 @@
+use super::super::tags;
 use crate::{entities, state};
 use axum::{
     extract::{Path, State},
@@ -6,7 +7,7 @@ use axum::{
     Json,
 };
 use entities::projects;
-use sea_orm::{ActiveModelTrait, DbErr, EntityTrait};
+use sea_orm::{ActiveModelTrait, DbErr, EntityTrait, TransactionTrait};
 use serde::{Deserialize, Serialize};
 use state::AppState;
 
@@ -15,17 +16,23 @@ pub enum UpdateProjectError {
     #[error("No project id({0}) found")]
     NoProjectFound(i32),
 
+    #[error("No tag id({0}) found")]
+    UnknownTag(i32),
+
     #[error("Db error: {0}")]
     DbError(#[from] DbErr),
 }
 
 impl IntoResponse for UpdateProjectError {
     fn into_response(self) -> Response {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("There was a problem: {}", self)
-        )
-            .into_response()
+        match self {
+            Self::UnknownTag(_) => (StatusCode::BAD_REQUEST, format!("Error: {}", self)).into_response(),
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("There was a problem: {}", self)
+            )
+                .into_response(),
+        }
     }
 }
 
@@ -43,6 +50,8 @@ pub struct UpdateProjectRequest {
 
     pub pictures: Option<Vec<String>>,
     pub videos: Option<Vec<String>>,
+    /* replaces every tag of the project */
+    pub tags: Option<Vec<i32>>,
 }
 
 pub async fn project(
@@ -81,7 +90,20 @@ pub async fn project(
         project.videos = sea_orm::Set(videos);
     }
 
-    let _res = project.update(&state.db_conn).await?;
+    if let Some(tag_ids) = &info.tags {
+        if let Some(id) = tags::find_unknown(&state.db_conn, tag_ids).await? {
+            return Err(UpdateProjectError::UnknownTag(id));
+        }
+    }
+
+    let txn = state.db_conn.begin().await?;
+    let _res = project.update(&txn).await?;
+
+    if let Some(tag_ids) = info.tags {
+        tags::assign(&txn, project_id, &tag_ids).await?;
+    }
+
+    txn.commit().await?;
 
     Ok(StatusCode::OK)
 }
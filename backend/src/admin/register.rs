@@ -1,54 +1,124 @@
-use axum::{body::Body, extract::State, http::StatusCode, response::{IntoResponse, Response}, Json};
-use reqwest::header;
-use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter};
+use axum::{extract::State, http::StatusCode, response::{IntoResponse, Response}, Json};
+use chrono::Utc;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DbErr, EntityTrait, QueryFilter, QuerySelect, SqlErr,
+    TransactionTrait,
+};
 use serde::Deserialize;
 use serde_json::json;
-use crate::{admin::auth::issue_jwt, entities, state::AppState};
+use crate::{
+    admin::{email, password, session, token},
+    entities::{self, invite},
+    state::AppState,
+};
 
+#[derive(thiserror::Error, Debug)]
+pub enum RegisterError {
+    #[error("Invite is invalid, expired or already used")]
+    InvalidInvite,
 
+    #[error("User already exists")]
+    UserExists,
+
+    #[error("Failed to hash password: {0}")]
+    PasswordError(#[from] password::PasswordError),
+
+    #[error("Db error: {0}")]
+    DbError(#[from] DbErr),
+
+    #[error("{0}")]
+    SessionError(#[from] session::SessionError),
+}
+
+impl IntoResponse for RegisterError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Self::InvalidInvite => StatusCode::FORBIDDEN,
+            Self::UserExists => StatusCode::CONFLICT,
+            Self::SessionError(e) => return e.into_response(),
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(json!({ "error": self.to_string() }))).into_response()
+    }
+}
+
+/// Registration is only possible by redeeming an invite created by an owner,
+/// the email and role come from the invite
 #[derive(Deserialize)]
 pub struct AddUserReqBody {
+    pub invite_token: String,
     pub name: Option<String>,
     pub last_name: Option<String>,
-    pub email: String,
     pub password: String,
 }
 
 pub async fn new_admin(
     State(state): State<AppState>,
     Json(user_info): Json<AddUserReqBody>,
-) -> Response<Body> {
+) -> Result<Response, RegisterError> {
     use entities::user;
 
-    let AddUserReqBody { name, last_name, email, password } = user_info; 
+    let AddUserReqBody { invite_token, name, last_name, password } = user_info; 
 
+    let now = Utc::now();
+
+    let txn = state.db_conn.begin().await?;
+
+    /* row lock so two requests can't redeem the same invite */
+    let invite = invite::Entity::find()
+        .filter(invite::Column::TokenHash.eq(token::digest(&invite_token)))
+        .filter(invite::Column::UsedAt.is_null())
+        .filter(invite::Column::ExpiresAt.gt(now))
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+        .ok_or(RegisterError::InvalidInvite)?;
+
+    /* invites from before emails were normalized may be mixed case */
+    let email = email::normalize(&invite.email);
     let existing_user = user::Entity::find()
         .filter(user::Column::Email.eq(&email))
-        .one(&state.db_conn)
-        .await;
+        .one(&txn)
+        .await?;
 
-    if let Ok(Some(_)) = existing_user {
-        return (StatusCode::CONFLICT, Json(json!({ "error": "User already exists" }))).into_response();
+    if existing_user.is_some() {
+        return Err(RegisterError::UserExists);
     }
 
+    /* only hashed for a valid invite, junk tokens must not cost bcrypt time */
+    let password = password::hash(password).await?;
+
     let new_user = user::ActiveModel {
         name: sea_orm::Set(name),
         last_name: sea_orm::Set(last_name),
         email: sea_orm::Set(email),
         password: sea_orm::Set(password),
+        role: sea_orm::Set(invite.role),
         ..Default::default()
-    };
-
-    match new_user.insert(&state.db_conn).await {
-        Ok(_user) => {
-            return Response::builder()
-                .status(StatusCode::CREATED)
-                .header(header::AUTHORIZATION, format!("Bearer {}", issue_jwt().unwrap()))
-                .body(Body::default())
-                .unwrap()
-        }
-        Err(_e) => {
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "Database insertion error" }))).into_response()
-        }
     }
+    .insert(&txn)
+    .await
+    /* a concurrent registration got past the check above */
+    .map_err(|err| match err.sql_err() {
+        Some(SqlErr::UniqueConstraintViolation(_)) => RegisterError::UserExists,
+        _ => err.into(),
+    })?;
+
+    let created_by = invite.created_by;
+    let mut invite: invite::ActiveModel = invite.into();
+    invite.used_at = sea_orm::Set(Some(now));
+    invite.used_by = sea_orm::Set(Some(new_user.id));
+    invite.update(&txn).await?;
+
+    txn.commit().await?;
+
+    tracing::info!(
+        "Admin {} ({:?}) registered through invite from user id {:?}",
+        new_user.email,
+        new_user.role,
+        created_by
+    );
+
+    let tokens = session::start(&state, &new_user).await?;
+    Ok((StatusCode::CREATED, tokens).into_response())
 }
@@ -0,0 +1,197 @@
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{TimeDelta, Utc};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, DbErr, EntityTrait, QueryFilter, QuerySelect,
+    Set, TransactionTrait,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::net::SocketAddr;
+use crate::{
+    entities::{password_reset, user},
+    mailer::Mail,
+    state::AppState,
+};
+use super::{email, password, session, throttle::Key, token};
+
+const RESET_VALID_FOR: TimeDelta = TimeDelta::hours(1);
+
+#[derive(thiserror::Error, Debug)]
+pub enum ResetError {
+    #[error("Reset token is invalid, expired or already used")]
+    InvalidToken,
+
+    #[error("Password must be at least {} characters long", password::MIN_LEN)]
+    WeakPassword,
+
+    #[error("Too many reset requests")]
+    Throttled(TimeDelta),
+
+    #[error("{0}")]
+    PasswordError(#[from] password::PasswordError),
+
+    #[error("Db error: {0}")]
+    DbError(#[from] DbErr),
+}
+
+impl IntoResponse for ResetError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Self::InvalidToken => StatusCode::FORBIDDEN,
+            Self::WeakPassword => StatusCode::BAD_REQUEST,
+            Self::Throttled(wait) => {
+                let secs = (wait.num_milliseconds() + 999) / 1000;
+                return (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(header::RETRY_AFTER, secs.to_string())],
+                    Json(json!({ "error": self.to_string(), "retry_after": secs })),
+                )
+                    .into_response();
+            }
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(json!({ "error": self.to_string() }))).into_response()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RequestReq {
+    email: String,
+}
+
+/// Always answers 202 so the endpoint can't be used to find admin emails.
+/// Every request counts against the email and the caller's ip, so after a
+/// few mails the throttle's back-off applies and the mailbox can't be flooded.
+/// The lookup and mail run in the background, otherwise known emails would
+/// answer noticeably slower than unknown ones.
+pub async fn request(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(body): Json<RequestReq>,
+) -> Result<StatusCode, ResetError> {
+    let email = email::normalize(&body.email);
+    let keys = [Key::Reset(format!("ip:{}", addr.ip())), Key::Reset(email.clone())];
+    if let Some(wait) = state.throttle.retry_after(&keys).await? {
+        return Err(ResetError::Throttled(wait));
+    }
+    state.throttle.failed(&keys).await?;
+
+    tokio::spawn(async move {
+        if let Err(e) = send_link(&state, &email).await {
+            tracing::error!("Failed to issue password reset: {e}");
+        }
+    });
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn send_link(state: &AppState, email: &str) -> Result<(), DbErr> {
+    let Some(user) = user::Entity::find()
+        .filter(user::Column::Email.eq(email))
+        .one(&state.db_conn)
+        .await?
+    else {
+        tracing::warn!("Password reset requested for unknown email {email}");
+        return Ok(());
+    };
+
+    let now = Utc::now();
+    let token = token::random();
+
+    /* only the newest link works */
+    password_reset::Entity::delete_many()
+        .filter(password_reset::Column::UserId.eq(user.id))
+        .filter(password_reset::Column::UsedAt.is_null())
+        .exec(&state.db_conn)
+        .await?;
+
+    password_reset::ActiveModel {
+        id: sea_orm::NotSet,
+        user_id: Set(user.id),
+        token_hash: Set(token::digest(&token)),
+        created_at: Set(now),
+        expires_at: Set(now + RESET_VALID_FOR),
+        used_at: Set(None),
+    }
+    .insert(&state.db_conn)
+    .await?;
+
+    let mail = Mail {
+        to: user.email,
+        subject: "Password reset".into(),
+        body: format!(
+            "Someone asked to reset the password of your admin account.\n\
+             Open {}/admin/?reset_token={token} within {} minutes to choose a new one,\n\
+             or ignore this mail if it wasn't you.",
+            state.public_url,
+            RESET_VALID_FOR.num_minutes()
+        ),
+    };
+
+    /* the token is stored already, a failed send is logged but not exposed */
+    if let Err(e) = state.mailer.send(mail).await {
+        tracing::error!("Failed to send password reset mail: {e}");
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmReq {
+    token: String,
+    new_password: String,
+}
+
+/// Sets the new password and ends every session of the user
+pub async fn confirm(
+    State(state): State<AppState>,
+    Json(body): Json<ConfirmReq>,
+) -> Result<StatusCode, ResetError> {
+    if body.new_password.chars().count() < password::MIN_LEN {
+        return Err(ResetError::WeakPassword);
+    }
+
+    let now = Utc::now();
+
+    let txn = state.db_conn.begin().await?;
+
+    let (reset, user) = password_reset::Entity::find()
+        .find_also_related(user::Entity)
+        .filter(password_reset::Column::TokenHash.eq(token::digest(&body.token)))
+        .filter(password_reset::Column::UsedAt.is_null())
+        .filter(password_reset::Column::ExpiresAt.gt(now))
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+        .ok_or(ResetError::InvalidToken)?;
+    let user = user.ok_or(ResetError::InvalidToken)?;
+
+    /* hashed only for a valid token, junk tokens must not cost bcrypt time */
+    let hash = password::hash(body.new_password).await?;
+
+    let mut reset: password_reset::ActiveModel = reset.into();
+    reset.used_at = Set(Some(now));
+    reset.update(&txn).await?;
+
+    user::Entity::update_many()
+        .col_expr(user::Column::Password, Expr::value(hash))
+        .filter(user::Column::Id.eq(user.id))
+        .exec(&txn)
+        .await?;
+
+    txn.commit().await?;
+
+    let revoked = session::revoke_all(&state.db_conn, user.id, None).await?;
+    if let Err(e) = state.throttle.succeeded(&Key::Email(user.email.clone())).await {
+        tracing::error!("Failed to reset login attempts: {e}");
+    }
+
+    tracing::info!("{} reset their password, {revoked} session(s) revoked", user.email);
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,235 @@
+use crate::{
+    entities::{refresh_token, spent_refresh_token, user},
+    state::AppState,
+};
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{TimeDelta, Utc};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait,
+    QueryFilter, Set, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{
+    auth::{issue_jwt, ACCESS_VALID_FOR},
+    token,
+};
+
+pub(crate) const REFRESH_VALID_FOR: TimeDelta = TimeDelta::days(30);
+
+#[derive(thiserror::Error, Debug)]
+pub enum SessionError {
+    #[error("Invalid, expired or revoked refresh token")]
+    InvalidToken,
+
+    #[error("Db error: {0}")]
+    DbError(#[from] DbErr),
+
+    #[error("Failed to issue token: {0}")]
+    JwtError(#[from] jsonwebtoken::errors::Error),
+}
+
+impl IntoResponse for SessionError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::InvalidToken => (StatusCode::UNAUTHORIZED, format!("Error: {}", self)).into_response(),
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("There was a problem: {}", self),
+            )
+                .into_response(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct TokensBody {
+    refresh_token: String,
+    expires_in: i64,
+}
+
+/// Short lived access token (sent in the `Authorization` header) together
+/// with the refresh token that can renew it
+pub struct Tokens {
+    pub access: String,
+    pub refresh: String,
+}
+
+impl IntoResponse for Tokens {
+    fn into_response(self) -> Response {
+        (
+            StatusCode::OK,
+            [(header::AUTHORIZATION, format!("Bearer {}", self.access))],
+            Json(TokensBody {
+                refresh_token: self.refresh,
+                expires_in: ACCESS_VALID_FOR.num_seconds(),
+            }),
+        )
+            .into_response()
+    }
+}
+
+/// Creates a server side session for `user` and issues its first tokens
+pub async fn start(state: &AppState, user: &user::Model) -> Result<Tokens, SessionError> {
+    let now = Utc::now();
+    let refresh = token::random();
+
+    let session = refresh_token::ActiveModel {
+        id: Set(Uuid::new_v4().to_string()),
+        user_id: Set(user.id),
+        token_hash: Set(token::digest(&refresh)),
+        created_at: Set(now),
+        expires_at: Set(now + REFRESH_VALID_FOR),
+        revoked_at: Set(None),
+    }
+    .insert(&state.db_conn)
+    .await?;
+
+    /* keep the table from growing with sessions nobody can use anymore */
+    refresh_token::Entity::delete_many()
+        .filter(refresh_token::Column::UserId.eq(user.id))
+        .filter(refresh_token::Column::ExpiresAt.lte(now))
+        .exec(&state.db_conn)
+        .await?;
+
+    Ok(Tokens {
+        access: issue_jwt(&state.keys, user, &session.id)?,
+        refresh,
+    })
+}
+
+/// Whether the session an access token belongs to is still alive and its
+/// user has not been disabled
+pub async fn is_active(db_conn: &DatabaseConnection, session_id: &str) -> Result<bool, DbErr> {
+    let session = refresh_token::Entity::find_by_id(session_id)
+        .inner_join(user::Entity)
+        .filter(user::Column::Disabled.eq(false))
+        .filter(refresh_token::Column::RevokedAt.is_null())
+        .filter(refresh_token::Column::ExpiresAt.gt(Utc::now()))
+        .one(db_conn)
+        .await?;
+
+    Ok(session.is_some())
+}
+
+/// Revokes every live session of `user_id`, optionally keeping one,
+/// returns how many were revoked
+pub async fn revoke_all(
+    db_conn: &DatabaseConnection,
+    user_id: i32,
+    except: Option<&str>,
+) -> Result<u64, DbErr> {
+    let mut query = refresh_token::Entity::update_many()
+        .col_expr(refresh_token::Column::RevokedAt, Expr::value(Utc::now()))
+        .filter(refresh_token::Column::UserId.eq(user_id))
+        .filter(refresh_token::Column::RevokedAt.is_null());
+
+    if let Some(session_id) = except {
+        query = query.filter(refresh_token::Column::Id.ne(session_id));
+    }
+
+    Ok(query.exec(db_conn).await?.rows_affected)
+}
+
+#[derive(Deserialize)]
+pub struct RefreshReq {
+    refresh_token: String,
+}
+
+/// Exchanges a refresh token for a new access token, the refresh token
+/// itself is rotated so a leaked one can only be used once. Presenting a
+/// token that was already rotated revokes its whole session.
+pub async fn refresh(
+    State(state): State<AppState>,
+    Json(body): Json<RefreshReq>,
+) -> Result<Tokens, SessionError> {
+    let now = Utc::now();
+    let old_hash = token::digest(&body.refresh_token);
+    let refresh = token::random();
+
+    let txn = state.db_conn.begin().await?;
+
+    /* conditional on the old hash, of two concurrent refreshes only one wins */
+    let rotated = refresh_token::Entity::update_many()
+        .col_expr(refresh_token::Column::TokenHash, Expr::value(token::digest(&refresh)))
+        .filter(refresh_token::Column::TokenHash.eq(&old_hash))
+        .filter(refresh_token::Column::RevokedAt.is_null())
+        .filter(refresh_token::Column::ExpiresAt.gt(now))
+        .exec_with_returning(&txn)
+        .await?;
+
+    let Some(session) = rotated.into_iter().next() else {
+        txn.rollback().await?;
+        revoke_reused(&state.db_conn, &old_hash).await?;
+        return Err(SessionError::InvalidToken);
+    };
+
+    spent_refresh_token::ActiveModel {
+        token_hash: Set(old_hash),
+        session_id: Set(session.id.clone()),
+        created_at: Set(now),
+    }
+    .insert(&txn)
+    .await?;
+
+    let user = user::Entity::find_by_id(session.user_id)
+        .one(&txn)
+        .await?
+        .filter(|u| !u.disabled)
+        .ok_or(SessionError::InvalidToken)?;
+
+    txn.commit().await?;
+
+    Ok(Tokens {
+        access: issue_jwt(&state.keys, &user, &session.id)?,
+        refresh,
+    })
+}
+
+/// Revokes the session `token_hash` was rotated out of, if it was
+async fn revoke_reused(db_conn: &DatabaseConnection, token_hash: &str) -> Result<(), DbErr> {
+    let Some(spent) = spent_refresh_token::Entity::find_by_id(token_hash)
+        .one(db_conn)
+        .await?
+    else {
+        return Ok(());
+    };
+
+    let res = refresh_token::Entity::update_many()
+        .col_expr(refresh_token::Column::RevokedAt, Expr::value(Utc::now()))
+        .filter(refresh_token::Column::Id.eq(&spent.session_id))
+        .filter(refresh_token::Column::RevokedAt.is_null())
+        .exec(db_conn)
+        .await?;
+
+    if res.rows_affected > 0 {
+        tracing::warn!("Rotated refresh token reused, session {} revoked", spent.session_id);
+    }
+
+    Ok(())
+}
+
+/// Revokes the session, access tokens issued for it stop working right away
+pub async fn logout(
+    State(state): State<AppState>,
+    Json(body): Json<RefreshReq>,
+) -> Result<StatusCode, SessionError> {
+    let res = refresh_token::Entity::update_many()
+        .col_expr(refresh_token::Column::RevokedAt, Expr::value(Utc::now()))
+        .filter(refresh_token::Column::TokenHash.eq(token::digest(&body.refresh_token)))
+        .filter(refresh_token::Column::RevokedAt.is_null())
+        .exec(&state.db_conn)
+        .await?;
+
+    if res.rows_affected == 0 {
+        return Err(SessionError::InvalidToken);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
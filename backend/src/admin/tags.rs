@@ -0,0 +1,225 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, PaginatorTrait,
+    QueryFilter, QueryOrder, Set, SqlErr,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::BTreeSet;
+use crate::{
+    entities::{project_tag, tag},
+    state::AppState,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum TagsError {
+    #[error("No tag id({0}) found")]
+    NotFound(i32),
+
+    #[error("Tag name can't be empty")]
+    EmptyName,
+
+    #[error("Slug `{0}` is already used by another tag")]
+    SlugTaken(String),
+
+    #[error("Db error: {0}")]
+    DbError(#[from] DbErr),
+}
+
+impl IntoResponse for TagsError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::EmptyName => StatusCode::BAD_REQUEST,
+            Self::SlugTaken(_) => StatusCode::CONFLICT,
+            Self::DbError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(json!({ "error": self.to_string() }))).into_response()
+    }
+}
+
+/// `Home & Garden` becomes `home-garden`, used in `?tag=` filters
+fn slugify(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// A concurrent create or rename can take the slug after `ensure_slug_free`,
+/// the unique index then rejects the write
+fn slug_conflict(slug: &str) -> impl FnOnce(DbErr) -> TagsError + '_ {
+    move |err| match err.sql_err() {
+        Some(SqlErr::UniqueConstraintViolation(_)) => TagsError::SlugTaken(slug.to_owned()),
+        _ => err.into(),
+    }
+}
+
+async fn ensure_slug_free<C: ConnectionTrait>(
+    db: &C,
+    slug: &str,
+    except: Option<i32>,
+) -> Result<(), TagsError> {
+    let mut query = tag::Entity::find().filter(tag::Column::Slug.eq(slug));
+    if let Some(id) = except {
+        query = query.filter(tag::Column::Id.ne(id));
+    }
+
+    if query.one(db).await?.is_some() {
+        return Err(TagsError::SlugTaken(slug.to_owned()));
+    }
+
+    Ok(())
+}
+
+/// First of `tag_ids` that doesn't exist, if any
+pub(crate) async fn find_unknown<C: ConnectionTrait>(
+    db: &C,
+    tag_ids: &[i32],
+) -> Result<Option<i32>, DbErr> {
+    let wanted: BTreeSet<i32> = tag_ids.iter().copied().collect();
+    let found: BTreeSet<i32> = tag::Entity::find()
+        .filter(tag::Column::Id.is_in(wanted.iter().copied()))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|t| t.id)
+        .collect();
+
+    Ok(wanted.difference(&found).next().copied())
+}
+
+/// Replaces the tags of a project, ids should be checked with `find_unknown`
+pub(crate) async fn assign<C: ConnectionTrait>(
+    db: &C,
+    project_id: i32,
+    tag_ids: &[i32],
+) -> Result<(), DbErr> {
+    project_tag::Entity::delete_many()
+        .filter(project_tag::Column::ProjectId.eq(project_id))
+        .exec(db)
+        .await?;
+
+    let rows: Vec<project_tag::ActiveModel> = tag_ids
+        .iter()
+        .copied()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|tag_id| project_tag::ActiveModel {
+            project_id: Set(project_id),
+            tag_id: Set(tag_id),
+        })
+        .collect();
+
+    if !rows.is_empty() {
+        project_tag::Entity::insert_many(rows).exec(db).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn list(State(state): State<AppState>) -> Result<Json<Vec<tag::Model>>, TagsError> {
+    let tags = tag::Entity::find()
+        .order_by_asc(tag::Column::Name)
+        .all(&state.db_conn)
+        .await?;
+
+    Ok(Json(tags))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateTagReq {
+    pub name: String,
+    /* derived from the name when missing */
+    pub slug: Option<String>,
+}
+
+pub async fn create(
+    State(state): State<AppState>,
+    Json(info): Json<CreateTagReq>,
+) -> Result<(StatusCode, Json<tag::Model>), TagsError> {
+    let name = info.name.trim().to_owned();
+    let slug = slugify(info.slug.as_deref().unwrap_or(&name));
+    if name.is_empty() || slug.is_empty() {
+        return Err(TagsError::EmptyName);
+    }
+
+    ensure_slug_free(&state.db_conn, &slug, None).await?;
+
+    let tag = tag::ActiveModel {
+        id: sea_orm::NotSet,
+        slug: Set(slug.clone()),
+        name: Set(name),
+    }
+    .insert(&state.db_conn)
+    .await
+    .map_err(slug_conflict(&slug))?;
+
+    Ok((StatusCode::CREATED, Json(tag)))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UpdateTagReq {
+    pub name: Option<String>,
+    pub slug: Option<String>,
+}
+
+pub async fn update(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+    Json(info): Json<UpdateTagReq>,
+) -> Result<Json<tag::Model>, TagsError> {
+    let existing = tag::Entity::find_by_id(id)
+        .one(&state.db_conn)
+        .await?
+        .ok_or(TagsError::NotFound(id))?;
+
+    let mut tag: tag::ActiveModel = existing.into();
+
+    if let Some(name) = info.name {
+        let name = name.trim().to_owned();
+        if name.is_empty() {
+            return Err(TagsError::EmptyName);
+        }
+        tag.name = Set(name);
+    }
+
+    /* the slug stays put on renames so shared links keep working */
+    if let Some(slug) = info.slug {
+        let slug = slugify(&slug);
+        if slug.is_empty() {
+            return Err(TagsError::EmptyName);
+        }
+        ensure_slug_free(&state.db_conn, &slug, Some(id)).await?;
+        tag.slug = Set(slug);
+    }
+
+    let slug = tag.slug.clone().unwrap();
+    Ok(Json(tag.update(&state.db_conn).await.map_err(slug_conflict(&slug))?))
+}
+
+pub async fn delete(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, TagsError> {
+    let used_by = project_tag::Entity::find()
+        .filter(project_tag::Column::TagId.eq(id))
+        .count(&state.db_conn)
+        .await?;
+
+    /* project_tag rows go with the tag (on delete cascade) */
+    let res = tag::Entity::delete_by_id(id).exec(&state.db_conn).await?;
+    if res.rows_affected == 0 {
+        return Err(TagsError::NotFound(id));
+    }
+
+    tracing::info!("Tag id({id}) deleted, removed from {used_by} project(s)");
+
+    Ok(StatusCode::NO_CONTENT)
+}
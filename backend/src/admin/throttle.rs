@@ -0,0 +1,264 @@
+use crate::entities::login_attempt;
+use async_trait::async_trait;
+use chrono::{DateTime, TimeDelta, Utc};
+use sea_orm::{
+    ConnectionTrait, DatabaseConnection, DbBackend, DbErr, EntityTrait, Statement,
+};
+use std::{
+    collections::HashMap,
+    env,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+};
+
+/* failures allowed before any back-off kicks in */
+const FREE_FAILURES: u32 = 3;
+const MAX_BACKOFF: TimeDelta = TimeDelta::minutes(15);
+/* consecutive failures on one email that lock the account */
+const LOCKOUT_AFTER: u32 = 10;
+const LOCKOUT_FOR: TimeDelta = TimeDelta::minutes(30);
+/* counters of keys that stopped failing are forgotten */
+const FORGET_AFTER: TimeDelta = TimeDelta::hours(1);
+/* in-memory store prunes forgotten keys once it grows past this */
+const MEMORY_PRUNE_AT: usize = 10_000;
+
+#[derive(Clone, Debug)]
+pub struct Attempt {
+    pub failures: u32,
+    pub last_failure: DateTime<Utc>,
+    pub blocked_until: Option<DateTime<Utc>>,
+}
+
+impl Attempt {
+    fn is_forgotten(&self, now: DateTime<Utc>) -> bool {
+        self.last_failure + FORGET_AFTER <= now
+            && self.blocked_until.map_or(true, |until| until <= now)
+    }
+}
+
+/// Where failed login counters live, keyed by strings like `ip:1.2.3.4`
+/// or `email:someone@example.com`
+#[async_trait]
+pub trait AttemptStore: Send + Sync {
+    async fn get(&self, key: &str) -> Result<Option<Attempt>, DbErr>;
+    /// Counts one more failure at `now` in a single step, so parallel
+    /// attempts can't overwrite each other, and returns the new count.
+    /// Forgotten counters start over at 1.
+    async fn record_failure(&self, key: &str, now: DateTime<Utc>) -> Result<u32, DbErr>;
+    /// Blocks `key` until `until`, never shortening a longer block
+    async fn block(&self, key: &str, until: DateTime<Utc>) -> Result<(), DbErr>;
+    async fn remove(&self, key: &str) -> Result<(), DbErr>;
+}
+
+/// Default store, counters are lost on restart and not shared between instances
+#[derive(Default)]
+pub struct MemoryStore {
+    attempts: Mutex<HashMap<String, Attempt>>,
+}
+
+#[async_trait]
+impl AttemptStore for MemoryStore {
+    async fn get(&self, key: &str) -> Result<Option<Attempt>, DbErr> {
+        Ok(self.attempts.lock().unwrap().get(key).cloned())
+    }
+
+    async fn record_failure(&self, key: &str, now: DateTime<Utc>) -> Result<u32, DbErr> {
+        let mut attempts = self.attempts.lock().unwrap();
+        if attempts.len() >= MEMORY_PRUNE_AT {
+            attempts.retain(|_, a| !a.is_forgotten(now));
+        }
+
+        let attempt = attempts
+            .entry(key.to_owned())
+            .and_modify(|a| {
+                a.failures = if a.is_forgotten(now) { 1 } else { a.failures + 1 };
+                a.last_failure = now;
+            })
+            .or_insert(Attempt { failures: 1, last_failure: now, blocked_until: None });
+
+        Ok(attempt.failures)
+    }
+
+    async fn block(&self, key: &str, until: DateTime<Utc>) -> Result<(), DbErr> {
+        if let Some(attempt) = self.attempts.lock().unwrap().get_mut(key) {
+            attempt.blocked_until = attempt.blocked_until.max(Some(until));
+        }
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> Result<(), DbErr> {
+        self.attempts.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+/// Keeps counters in the `login_attempt` table, shared by every instance
+pub struct DbStore {
+    db_conn: DatabaseConnection,
+}
+
+impl DbStore {
+    pub fn new(db_conn: DatabaseConnection) -> Self {
+        Self { db_conn }
+    }
+}
+
+#[async_trait]
+impl AttemptStore for DbStore {
+    async fn get(&self, key: &str) -> Result<Option<Attempt>, DbErr> {
+        Ok(login_attempt::Entity::find_by_id(key)
+            .one(&self.db_conn)
+            .await?
+            .map(|m| Attempt {
+                failures: m.failures.max(0) as u32,
+                last_failure: m.last_failure,
+                blocked_until: m.blocked_until,
+            }))
+    }
+
+    async fn record_failure(&self, key: &str, now: DateTime<Utc>) -> Result<u32, DbErr> {
+        /* mirrors `Attempt::is_forgotten` */
+        let row = self
+            .db_conn
+            .query_one(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                r#"INSERT INTO "login_attempt" ("key", "failures", "last_failure")
+                VALUES ($1, 1, $2)
+                ON CONFLICT ("key") DO UPDATE SET
+                    "failures" = CASE
+                        WHEN "login_attempt"."last_failure" <= $3
+                            AND ("login_attempt"."blocked_until" IS NULL
+                                OR "login_attempt"."blocked_until" <= $2)
+                        THEN 1
+                        ELSE "login_attempt"."failures" + 1
+                    END,
+                    "last_failure" = $2
+                RETURNING "failures""#,
+                [key.into(), now.into(), (now - FORGET_AFTER).into()],
+            ))
+            .await?
+            .ok_or_else(|| DbErr::RecordNotFound(key.to_owned()))?;
+
+        let failures: i32 = row.try_get("", "failures")?;
+        Ok(failures.max(0) as u32)
+    }
+
+    async fn block(&self, key: &str, until: DateTime<Utc>) -> Result<(), DbErr> {
+        self.db_conn
+            .execute(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                r#"UPDATE "login_attempt"
+                SET "blocked_until" = GREATEST("blocked_until", $2)
+                WHERE "key" = $1"#,
+                [key.into(), until.into()],
+            ))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> Result<(), DbErr> {
+        login_attempt::Entity::delete_by_id(key)
+            .exec(&self.db_conn)
+            .await?;
+        Ok(())
+    }
+}
+
+/// What a login attempt is counted against
+pub enum Key {
+    Ip(IpAddr),
+    Email(String),
+    /* passcode attempts on a visitor link, by uuid */
+    Visitor(String),
+    /* password reset mails, by email or ip, kept apart from login counters */
+    Reset(String),
+}
+
+impl Key {
+    fn id(&self) -> String {
+        match self {
+            Self::Ip(ip) => format!("ip:{ip}"),
+            Self::Email(email) => format!("email:{}", email.to_lowercase()),
+            Self::Visitor(uuid) => format!("visitor:{uuid}"),
+            Self::Reset(id) => format!("reset:{}", id.to_lowercase()),
+        }
+    }
+
+    /* an IP is only slowed down, an account or link gets locked */
+    fn locks_account(&self) -> bool {
+        matches!(self, Self::Email(_) | Self::Visitor(_))
+    }
+}
+
+fn backoff(failures: u32) -> TimeDelta {
+    if failures <= FREE_FAILURES {
+        return TimeDelta::zero();
+    }
+
+    let exp = (failures - FREE_FAILURES).min(16);
+    TimeDelta::seconds(1 << exp).min(MAX_BACKOFF)
+}
+
+/// Exponential back-off and temporary lockout for `/admin/auth` and
+/// visitor passcodes
+#[derive(Clone)]
+pub struct Throttle {
+    store: Arc<dyn AttemptStore>,
+}
+
+impl Throttle {
+    pub fn new(store: Arc<dyn AttemptStore>) -> Self {
+        Self { store }
+    }
+
+    /// `LOGIN_ATTEMPT_STORE=database` shares counters through the database,
+    /// anything else keeps them in memory
+    pub fn from_env(db_conn: &DatabaseConnection) -> Self {
+        match env::var("LOGIN_ATTEMPT_STORE").as_deref() {
+            Ok("database") => Self::new(Arc::new(DbStore::new(db_conn.clone()))),
+            _ => Self::new(Arc::new(MemoryStore::default())),
+        }
+    }
+
+    /// How long the caller has to wait before trying again, if at all
+    pub async fn retry_after(&self, keys: &[Key]) -> Result<Option<TimeDelta>, DbErr> {
+        let now = Utc::now();
+        let mut wait = TimeDelta::zero();
+
+        for key in keys {
+            if let Some(Attempt { blocked_until: Some(until), .. }) = self.store.get(&key.id()).await? {
+                wait = wait.max(until - now);
+            }
+        }
+
+        Ok((wait > TimeDelta::zero()).then_some(wait))
+    }
+
+    /// Counts a failed attempt against every key, returns the resulting wait
+    pub async fn failed(&self, keys: &[Key]) -> Result<Option<TimeDelta>, DbErr> {
+        let now = Utc::now();
+
+        for key in keys {
+            let id = key.id();
+            let failures = self.store.record_failure(&id, now).await?;
+
+            let blocked_for = if key.locks_account() && failures >= LOCKOUT_AFTER {
+                tracing::warn!("Locking {id} after {failures} failed logins");
+                LOCKOUT_FOR
+            } else {
+                backoff(failures)
+            };
+
+            if blocked_for > TimeDelta::zero() {
+                self.store.block(&id, now + blocked_for).await?;
+            }
+        }
+
+        self.retry_after(keys).await
+    }
+
+    pub async fn succeeded(&self, key: &Key) -> Result<(), DbErr> {
+        self.store.remove(&key.id()).await
+    }
+}
@@ -0,0 +1,525 @@
+use crate::{entities::user, state::AppState};
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Nonce,
+};
+use axum::{
+    extract::{ConnectInfo, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{TimeDelta, Utc};
+use hmac::{Hmac, Mac};
+use jsonwebtoken::{decode, decode_header, encode, Header, Validation};
+use lazy_static::lazy_static;
+use rand::{seq::SliceRandom, Rng};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, Condition, DbErr, EntityTrait, QueryFilter,
+    Set,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::{env, net::SocketAddr};
+
+use super::{
+    password,
+    session::{self, SessionError},
+    throttle::Key,
+    token,
+    verify::AdminIdentity,
+};
+
+const ISSUER: &str = "Portfolio Admin";
+const STEP_SECS: i64 = 30;
+const DIGITS: u32 = 6;
+/* accept codes one step early or late to tolerate clock drift */
+const SKEW_STEPS: i64 = 1;
+const SECRET_LEN: usize = 20;
+const RECOVERY_CODES: usize = 10;
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+/* time between a correct password and the second factor */
+const MFA_VALID_FOR: TimeDelta = TimeDelta::minutes(5);
+const MFA_PURPOSE: &str = "mfa";
+
+lazy_static! {
+    /// `TOTP_ENCRYPTION_KEY` stretched to an AES-256 key, enrollment is
+    /// refused when it is not set
+    static ref CIPHER: Option<Aes256Gcm> = env::var("TOTP_ENCRYPTION_KEY")
+        .ok()
+        .map(|key| Aes256Gcm::new(&Sha256::digest(key.as_bytes())));
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum TotpError {
+    #[error("TOTP is not configured on this server (TOTP_ENCRYPTION_KEY)")]
+    NotConfigured,
+
+    #[error("Invalid code")]
+    InvalidCode,
+
+    #[error("Invalid or expired mfa token")]
+    InvalidMfaToken,
+
+    #[error("Two-factor authentication is already enabled")]
+    AlreadyEnabled,
+
+    #[error("Two-factor authentication is not enabled")]
+    NotEnabled,
+
+    #[error("No enrollment in progress, start one first")]
+    NoPendingEnrollment,
+
+    #[error("Invalid password")]
+    InvalidPassword,
+
+    #[error("User no longer exists")]
+    UserNotFound,
+
+    #[error("Too many failed attempts")]
+    Throttled(TimeDelta),
+
+    #[error("Failed to decrypt the stored secret")]
+    Crypto,
+
+    #[error("Failed to issue token: {0}")]
+    JwtError(#[from] jsonwebtoken::errors::Error),
+
+    #[error("{0}")]
+    PasswordError(#[from] password::PasswordError),
+
+    #[error("{0}")]
+    SessionError(#[from] SessionError),
+
+    #[error("Db error: {0}")]
+    DbError(#[from] DbErr),
+}
+
+impl IntoResponse for TotpError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Self::InvalidCode | Self::InvalidMfaToken | Self::InvalidPassword => StatusCode::UNAUTHORIZED,
+            Self::AlreadyEnabled | Self::NotEnabled | Self::NoPendingEnrollment => StatusCode::CONFLICT,
+            Self::NotConfigured => StatusCode::SERVICE_UNAVAILABLE,
+            Self::UserNotFound => StatusCode::NOT_FOUND,
+            Self::Throttled(wait) => {
+                let secs = (wait.num_milliseconds() + 999) / 1000;
+                return (
+                    StatusCode::UNAUTHORIZED,
+                    [(axum::http::header::RETRY_AFTER, secs.to_string())],
+                    Json(json!({ "error": self.to_string(), "retry_after": secs })),
+                )
+                    .into_response();
+            }
+            Self::SessionError(e) => return e.into_response(),
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(json!({ "error": self.to_string() }))).into_response()
+    }
+}
+
+fn cipher() -> Result<&'static Aes256Gcm, TotpError> {
+    CIPHER.as_ref().ok_or(TotpError::NotConfigured)
+}
+
+/* stored as base64(nonce || ciphertext) */
+fn encrypt(secret: &[u8]) -> Result<String, TotpError> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher()?
+        .encrypt(&nonce, secret)
+        .map_err(|_| TotpError::Crypto)?;
+
+    let mut out = nonce.to_vec();
+    out.extend_from_slice(&ciphertext);
+    Ok(BASE64.encode(out))
+}
+
+fn decrypt(stored: &str) -> Result<Vec<u8>, TotpError> {
+    let bytes = BASE64.decode(stored).map_err(|_| TotpError::Crypto)?;
+    if bytes.len() < 12 {
+        return Err(TotpError::Crypto);
+    }
+
+    let (nonce, ciphertext) = bytes.split_at(12);
+    cipher()?
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| TotpError::Crypto)
+}
+
+/// RFC 4226 HOTP value for `counter`, truncated to [`DIGITS`] digits
+fn hotp(secret: &[u8], counter: u64) -> u32 {
+    let mut mac = <Hmac<Sha1> as Mac>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let code = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+
+    code % 10u32.pow(DIGITS)
+}
+
+/// RFC 6238 check at unix time `now`, returns the matched time step so it
+/// can't be replayed
+fn check_code(secret: &[u8], code: &str, last_step: Option<i64>, now: i64) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != DIGITS as usize {
+        return None;
+    }
+    let code: u32 = code.parse().ok()?;
+    let now_step = now / STEP_SECS;
+
+    (now_step - SKEW_STEPS..=now_step + SKEW_STEPS)
+        .filter(|step| last_step.map_or(true, |last| *step > last))
+        .find(|step| hotp(secret, *step as u64) == code)
+}
+
+fn provisioning_uri(secret: &[u8], email: &str) -> String {
+    let secret = base32::encode(base32::Alphabet::Rfc4648 { padding: false }, secret);
+    format!(
+        "otpauth://totp/{}:{}?secret={secret}&issuer={}&algorithm=SHA1&digits={DIGITS}&period={STEP_SECS}",
+        urlencoding::encode(ISSUER),
+        urlencoding::encode(email),
+        urlencoding::encode(ISSUER),
+    )
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn new_recovery_codes() -> Vec<String> {
+    let mut rng = rand::thread_rng();
+    (0..RECOVERY_CODES)
+        .map(|_| {
+            let code: String = (0..10)
+                .map(|_| *RECOVERY_CODE_ALPHABET.choose(&mut rng).unwrap() as char)
+                .collect();
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
+
+#[derive(Serialize, Deserialize)]
+struct MfaClaims {
+    exp: usize,
+    sub: i32,
+    purpose: String,
+}
+
+#[derive(Serialize)]
+struct ChallengeResponse {
+    mfa_required: bool,
+    mfa_token: String,
+    expires_in: i64,
+}
+
+/// Second half of `/admin/auth` for users with TOTP enabled: instead of a
+/// session the client gets a short lived token to present with the code
+pub fn challenge(state: &AppState, user: &user::Model) -> Result<Response, TotpError> {
+    let (kid, key) = state.keys.encoding_key();
+    let mfa_token = encode(
+        &Header { kid: Some(kid), ..Default::default() },
+        &MfaClaims {
+            exp: (Utc::now() + MFA_VALID_FOR).timestamp() as usize,
+            sub: user.id,
+            purpose: MFA_PURPOSE.into(),
+        },
+        &key,
+    )?;
+
+    Ok((
+        StatusCode::OK,
+        Json(ChallengeResponse {
+            mfa_required: true,
+            mfa_token,
+            expires_in: MFA_VALID_FOR.num_seconds(),
+        }),
+    )
+        .into_response())
+}
+
+async fn mfa_user(state: &AppState, mfa_token: &str) -> Result<user::Model, TotpError> {
+    let kid = decode_header(mfa_token)
+        .ok()
+        .and_then(|h| h.kid)
+        .ok_or(TotpError::InvalidMfaToken)?;
+    let key = state
+        .keys
+        .decoding_key(&state.db_conn, &kid)
+        .await
+        .ok_or(TotpError::InvalidMfaToken)?;
+    let claims = decode::<MfaClaims>(mfa_token, &key, &Validation::default())
+        .map_err(|_| TotpError::InvalidMfaToken)?
+        .claims;
+
+    if claims.purpose != MFA_PURPOSE {
+        return Err(TotpError::InvalidMfaToken);
+    }
+
+    user::Entity::find_by_id(claims.sub)
+        .one(&state.db_conn)
+        .await?
+        .filter(|u| u.totp_enabled && !u.disabled)
+        .ok_or(TotpError::InvalidMfaToken)
+}
+
+#[derive(Deserialize)]
+pub struct VerifyReq {
+    mfa_token: String,
+    code: Option<String>,
+    /* used instead of `code` when the authenticator is lost */
+    recovery_code: Option<String>,
+}
+
+pub async fn verify(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(body): Json<VerifyReq>,
+) -> Result<Response, TotpError> {
+    let user = mfa_user(&state, &body.mfa_token).await?;
+    let keys = [Key::Ip(addr.ip()), Key::Email(user.email.clone())];
+
+    if let Some(wait) = state.throttle.retry_after(&keys).await? {
+        return Err(TotpError::Throttled(wait));
+    }
+
+    /* the updates are conditional, of two requests with the same code or
+       recovery code only one gets through */
+    let is_valid = match (&body.code, &body.recovery_code) {
+        (Some(code), _) => {
+            let secret = decrypt(user.totp_secret.as_deref().ok_or(TotpError::NotEnabled)?)?;
+            match check_code(&secret, code, user.totp_last_step, Utc::now().timestamp()) {
+                Some(step) => {
+                    let res = user::Entity::update_many()
+                        .col_expr(user::Column::TotpLastStep, Expr::value(step))
+                        .filter(user::Column::Id.eq(user.id))
+                        .filter(
+                            Condition::any()
+                                .add(user::Column::TotpLastStep.is_null())
+                                .add(user::Column::TotpLastStep.lt(step)),
+                        )
+                        .exec(&state.db_conn)
+                        .await?;
+                    res.rows_affected == 1
+                }
+                None => false,
+            }
+        }
+        (None, Some(recovery_code)) => {
+            let digest = token::digest(&normalize_recovery_code(recovery_code));
+            let updated = user::Entity::update_many()
+                .col_expr(
+                    user::Column::TotpRecoveryCodes,
+                    Expr::cust_with_values(r#"array_remove("totp_recovery_codes", $1)"#, [digest.clone()]),
+                )
+                .filter(user::Column::Id.eq(user.id))
+                .filter(Expr::cust_with_values(r#"$1 = ANY("totp_recovery_codes")"#, [digest]))
+                .exec_with_returning(&state.db_conn)
+                .await?;
+
+            /* every recovery code works exactly once */
+            match updated.first() {
+                Some(updated) => {
+                    tracing::warn!(
+                        "{} used a recovery code, {} left",
+                        user.email,
+                        updated.totp_recovery_codes.len()
+                    );
+                    true
+                }
+                None => false,
+            }
+        }
+        (None, None) => false,
+    };
+
+    if !is_valid {
+        tracing::warn!("Failed second factor for {} from {}", user.email, addr.ip());
+        return Err(match state.throttle.failed(&keys).await? {
+            Some(wait) => TotpError::Throttled(wait),
+            None => TotpError::InvalidCode,
+        });
+    }
+
+    let [_, email] = &keys;
+    state.throttle.succeeded(email).await?;
+
+    Ok(session::start(&state, &user).await?.into_response())
+}
+
+#[derive(Serialize)]
+pub struct EnrollResponse {
+    /* base32, for authenticators that can't scan the uri */
+    pub secret: String,
+    pub provisioning_uri: String,
+}
+
+/// Starts (or restarts) enrollment, 2FA is only enforced after [`confirm`]
+pub async fn enroll(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+) -> Result<Json<EnrollResponse>, TotpError> {
+    let user = user::Entity::find_by_id(identity.id)
+        .one(&state.db_conn)
+        .await?
+        .ok_or(TotpError::UserNotFound)?;
+
+    if user.totp_enabled {
+        return Err(TotpError::AlreadyEnabled);
+    }
+
+    let secret: [u8; SECRET_LEN] = rand::thread_rng().gen();
+    let provisioning_uri = provisioning_uri(&secret, &user.email);
+
+    let mut user: user::ActiveModel = user.into();
+    user.totp_secret = Set(Some(encrypt(&secret)?));
+    user.totp_last_step = Set(None);
+    user.update(&state.db_conn).await?;
+
+    Ok(Json(EnrollResponse {
+        secret: base32::encode(base32::Alphabet::Rfc4648 { padding: false }, &secret),
+        provisioning_uri,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmReq {
+    code: String,
+}
+
+#[derive(Serialize)]
+pub struct ConfirmResponse {
+    /* only ever shown here, the database keeps digests */
+    pub recovery_codes: Vec<String>,
+}
+
+pub async fn confirm(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+    Json(body): Json<ConfirmReq>,
+) -> Result<Json<ConfirmResponse>, TotpError> {
+    let user = user::Entity::find_by_id(identity.id)
+        .one(&state.db_conn)
+        .await?
+        .ok_or(TotpError::UserNotFound)?;
+
+    if user.totp_enabled {
+        return Err(TotpError::AlreadyEnabled);
+    }
+
+    let secret = decrypt(user.totp_secret.as_deref().ok_or(TotpError::NoPendingEnrollment)?)?;
+    let step = check_code(&secret, &body.code, None, Utc::now().timestamp()).ok_or(TotpError::InvalidCode)?;
+
+    let recovery_codes = new_recovery_codes();
+    let mut user: user::ActiveModel = user.into();
+    user.totp_enabled = Set(true);
+    user.totp_last_step = Set(Some(step));
+    user.totp_recovery_codes = Set(recovery_codes
+        .iter()
+        .map(|c| token::digest(&normalize_recovery_code(c)))
+        .collect());
+    user.update(&state.db_conn).await?;
+
+    tracing::info!("{} enabled two-factor authentication", identity.email);
+
+    Ok(Json(ConfirmResponse { recovery_codes }))
+}
+
+#[derive(Deserialize)]
+pub struct DisableReq {
+    password: String,
+}
+
+pub async fn disable(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+    Json(body): Json<DisableReq>,
+) -> Result<StatusCode, TotpError> {
+    let user = user::Entity::find_by_id(identity.id)
+        .one(&state.db_conn)
+        .await?
+        .ok_or(TotpError::UserNotFound)?;
+
+    if !user.totp_enabled && user.totp_secret.is_none() {
+        return Err(TotpError::NotEnabled);
+    }
+
+    if !password::verify(body.password, Some(user.password.clone())).await? {
+        return Err(TotpError::InvalidPassword);
+    }
+
+    let mut user: user::ActiveModel = user.into();
+    user.totp_secret = Set(None);
+    user.totp_enabled = Set(false);
+    user.totp_recovery_codes = Set(vec![]);
+    user.totp_last_step = Set(None);
+    user.update(&state.db_conn).await?;
+
+    tracing::info!("{} disabled two-factor authentication", identity.email);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* RFC 4226 appendix D and RFC 6238 appendix B use this ASCII secret */
+    const SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn hotp_matches_rfc_4226() {
+        let expected = [
+            755224, 287082, 359152, 969429, 338314, 254676, 287922, 162583, 399871, 520489,
+        ];
+        for (counter, code) in expected.into_iter().enumerate() {
+            assert_eq!(hotp(SECRET, counter as u64), code, "counter {counter}");
+        }
+    }
+
+    #[test]
+    fn check_code_matches_rfc_6238() {
+        /* the RFC lists 8 digits, these are their last 6 */
+        let vectors = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+            (20000000000, "353130"),
+        ];
+        for (time, code) in vectors {
+            assert_eq!(check_code(SECRET, code, None, time), Some(time / STEP_SECS), "time {time}");
+        }
+    }
+
+    #[test]
+    fn check_code_tolerates_skew() {
+        let step = 1111111109 / STEP_SECS;
+        assert_eq!(check_code(SECRET, "081804", None, 1111111109 + STEP_SECS), Some(step));
+        assert_eq!(check_code(SECRET, "081804", None, 1111111109 - STEP_SECS), Some(step));
+        assert_eq!(check_code(SECRET, "081804", None, 1111111109 + 2 * STEP_SECS), None);
+    }
+
+    #[test]
+    fn check_code_rejects_replays_and_junk() {
+        let step = 1111111109 / STEP_SECS;
+        assert_eq!(check_code(SECRET, "081804", Some(step), 1111111109), None);
+        assert_eq!(check_code(SECRET, "081804", Some(step - 1), 1111111109), Some(step));
+        assert_eq!(check_code(SECRET, " 081804 ", None, 1111111109), Some(step));
+        assert_eq!(check_code(SECRET, "81804", None, 1111111109), None);
+        assert_eq!(check_code(SECRET, "08180x", None, 1111111109), None);
+        assert_eq!(check_code(SECRET, "000000", None, 1111111109), None);
+    }
+}
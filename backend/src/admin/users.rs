@@ -0,0 +1,201 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseTransaction, DbErr, EntityTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set, SqlErr, TransactionTrait,
+};
+use serde::Deserialize;
+use serde_json::json;
+use crate::{
+    entities::{sea_orm_active_enums::Role, user},
+    state::AppState,
+};
+use super::{email, me::Profile, session, verify::AdminIdentity};
+
+#[derive(thiserror::Error, Debug)]
+pub enum UsersError {
+    #[error("No user id({0}) found")]
+    NotFound(i32),
+
+    #[error("The last enabled owner can't be removed, demoted or disabled")]
+    LastOwner,
+
+    #[error("Email is already used by another admin")]
+    EmailTaken,
+
+    #[error("Db error: {0}")]
+    DbError(#[from] DbErr),
+}
+
+impl IntoResponse for UsersError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::LastOwner | Self::EmailTaken => StatusCode::CONFLICT,
+            Self::DbError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(json!({ "error": self.to_string() }))).into_response()
+    }
+}
+
+fn is_active_owner(user: &user::Model) -> bool {
+    user.role == Role::Owner && !user.disabled
+}
+
+/// Locks the active owners in id order. Has to run before any other user
+/// row is locked, so two owners demoting each other wait instead of deadlocking
+async fn lock_owners(txn: &DatabaseTransaction) -> Result<Vec<user::Model>, DbErr> {
+    user::Entity::find()
+        .filter(user::Column::Role.eq(Role::Owner))
+        .filter(user::Column::Disabled.eq(false))
+        .order_by_asc(user::Column::Id)
+        .lock_exclusive()
+        .all(txn)
+        .await
+}
+
+/// Fails unless some owner other than `id` can still manage the panel
+fn ensure_other_owner(owners: &[user::Model], id: i32) -> Result<(), UsersError> {
+    if owners.iter().all(|owner| owner.id == id) {
+        return Err(UsersError::LastOwner);
+    }
+
+    Ok(())
+}
+
+pub async fn list(State(state): State<AppState>) -> Result<Json<Vec<Profile>>, UsersError> {
+    let users = user::Entity::find()
+        .order_by_asc(user::Column::Id)
+        .all(&state.db_conn)
+        .await?;
+
+    Ok(Json(users.into_iter().map(Profile::from).collect()))
+}
+
+pub async fn get(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<Json<Profile>, UsersError> {
+    let user = user::Entity::find_by_id(id)
+        .one(&state.db_conn)
+        .await?
+        .ok_or(UsersError::NotFound(id))?;
+
+    Ok(Json(Profile::from(user)))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UpdateUserReq {
+    pub name: Option<String>,
+    pub last_name: Option<String>,
+    pub email: Option<String>,
+    pub role: Option<Role>,
+    pub disabled: Option<bool>,
+}
+
+/// Changing the role or disabling ends the user's sessions, so the new
+/// permissions apply right away instead of when the access token expires
+pub async fn update(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+    Path(id): Path<i32>,
+    Json(info): Json<UpdateUserReq>,
+) -> Result<Json<Profile>, UsersError> {
+    let txn = state.db_conn.begin().await?;
+    let owners = lock_owners(&txn).await?;
+
+    let existing = user::Entity::find_by_id(id)
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+        .ok_or(UsersError::NotFound(id))?;
+
+    let role = info.role.unwrap_or(existing.role);
+    let disabled = info.disabled.unwrap_or(existing.disabled);
+    let loses_owner = is_active_owner(&existing) && (role != Role::Owner || disabled);
+    if loses_owner {
+        ensure_other_owner(&owners, id)?;
+    }
+
+    let new_email = info.email.as_deref().map(email::normalize);
+    if let Some(email) = &new_email {
+        let taken = user::Entity::find()
+            .filter(user::Column::Email.eq(email))
+            .filter(user::Column::Id.ne(id))
+            .one(&txn)
+            .await?;
+
+        if taken.is_some() {
+            return Err(UsersError::EmailTaken);
+        }
+    }
+
+    let ends_sessions = role != existing.role || (disabled && !existing.disabled);
+    let mut user: user::ActiveModel = existing.into();
+
+    if let Some(name) = info.name {
+        user.name = Set(Some(name));
+    }
+
+    if let Some(last_name) = info.last_name {
+        user.last_name = Set(Some(last_name));
+    }
+
+    if let Some(email) = new_email {
+        user.email = Set(email);
+    }
+
+    user.role = Set(role);
+    user.disabled = Set(disabled);
+
+    /* the unique index catches a concurrent change to the same email */
+    let user = user.update(&txn).await.map_err(|err| match err.sql_err() {
+        Some(SqlErr::UniqueConstraintViolation(_)) => UsersError::EmailTaken,
+        _ => err.into(),
+    })?;
+    txn.commit().await?;
+
+    if ends_sessions {
+        let revoked = session::revoke_all(&state.db_conn, id, None).await?;
+        tracing::info!(
+            "{} changed {} to {:?}{}, {revoked} session(s) revoked",
+            identity.email,
+            user.email,
+            user.role,
+            if user.disabled { " (disabled)" } else { "" }
+        );
+    }
+
+    Ok(Json(Profile::from(user)))
+}
+
+pub async fn delete(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, UsersError> {
+    let txn = state.db_conn.begin().await?;
+    let owners = lock_owners(&txn).await?;
+
+    let existing = user::Entity::find_by_id(id)
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+        .ok_or(UsersError::NotFound(id))?;
+
+    if is_active_owner(&existing) {
+        ensure_other_owner(&owners, id)?;
+    }
+
+    /* sessions and reset tokens go with the row (on delete cascade) */
+    user::Entity::delete_by_id(id).exec(&txn).await?;
+    txn.commit().await?;
+
+    tracing::info!("{} deleted admin {}", identity.email, existing.email);
+
+    Ok(StatusCode::NO_CONTENT)
+}
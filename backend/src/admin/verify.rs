@@ -1,5 +1,6 @@
 use axum::{
     body::Body,
+    extract::State,
     http::{Request, StatusCode},
     middleware::Next,
     response::IntoResponse,
@@ -9,40 +10,91 @@ use axum_extra::{
     TypedHeader,
 };
 use chrono::Utc;
-use jsonwebtoken::{decode, DecodingKey, Validation};
-use crate::state;
-use super::JwtClaims;
+use jsonwebtoken::{decode, decode_header, Validation};
+use crate::{entities::sea_orm_active_enums::Role, state::AppState};
+use super::{session, JwtClaims};
+
+/// Who made the request, inserted into the request extensions by [`is_admin`]
+#[derive(Clone, Debug)]
+pub struct AdminIdentity {
+    pub id: i32,
+    pub email: String,
+    pub role: Role,
+    pub session_id: String,
+}
 
 pub async fn is_admin(
+    State(state): State<AppState>,
     bearer: Option<TypedHeader<Authorization<Bearer>>>,
-    req: Request<Body>,
+    mut req: Request<Body>,
     next: Next,
 ) -> impl axum::response::IntoResponse {
-    if !cfg!(debug_assertions) && bearer.is_none() {
-        /* require authentication in release mode */
+    let Some(TypedHeader(Authorization(token))) = bearer else {
         tracing::warn!("No bearer token");
         return StatusCode::UNAUTHORIZED.into_response();
-    }
+    };
+
+    /* tokens without a `kid` were signed by the old per-process key */
+    let key = match decode_header(token.token()).ok().and_then(|h| h.kid) {
+        Some(kid) => state.keys.decoding_key(&state.db_conn, &kid).await,
+        None => None,
+    };
+    let Some(key) = key else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
 
-    if let Some(TypedHeader(Authorization(token))) = bearer {
-        let claims = if let Ok(claims) = decode::<JwtClaims>(
-            token.token(),
-            &DecodingKey::from_secret(state::SECRET_KEY.as_bytes()),
-            &Validation::default(),
-        ) {
-            claims
-        } else {
-            return StatusCode::UNAUTHORIZED.into_response();
-        };
+    let claims = if let Ok(claims) = decode::<JwtClaims>(
+        token.token(),
+        &key,
+        &Validation::default(),
+    ) {
+        claims
+    } else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
 
-        if claims.claims.exp >= Utc::now().timestamp() as usize {
+    if claims.claims.exp < Utc::now().timestamp() as usize {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    /* the session may have been revoked since the token was issued */
+    match session::is_active(&state.db_conn, &claims.claims.sid).await {
+        Ok(true) => {
+            let JwtClaims { sub, email, role, sid, .. } = claims.claims;
+            req.extensions_mut().insert(AdminIdentity {
+                id: sub,
+                email,
+                role,
+                session_id: sid,
+            });
             next.run(req).await
-        } else {
-            StatusCode::UNAUTHORIZED.into_response()
+        },
+        Ok(false) => StatusCode::UNAUTHORIZED.into_response(),
+        Err(e) => {
+            tracing::error!("Failed to check session: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
         }
-    } else {
-        /* should not happen in release ever */
-        tracing::warn!("No bearer token, although debug mode");
-        next.run(req).await
+    }
+}
+
+/// Route guard, has to run after [`is_admin`] so the identity is known
+pub async fn require_role(
+    State(required): State<Role>,
+    req: Request<Body>,
+    next: Next,
+) -> impl axum::response::IntoResponse {
+    match req.extensions().get::<AdminIdentity>() {
+        Some(identity) if identity.role >= required => next.run(req).await,
+        Some(identity) => {
+            tracing::warn!(
+                "{} ({:?}) tried to access {} which requires {:?}",
+                identity.email,
+                identity.role,
+                req.uri(),
+                required
+            );
+            StatusCode::FORBIDDEN.into_response()
+        }
+        None => StatusCode::UNAUTHORIZED.into_response(),
     }
 }
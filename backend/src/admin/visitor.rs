@@ -1,45 +1,516 @@
 use axum::{
-    extract::State,
+    extract::{Path, Query, State},
     http::StatusCode,
-    response::IntoResponse,
-    Json,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use sea_orm::{
+    sea_query::{Alias, Expr}, ActiveModelTrait, ColumnTrait, DbErr, EntityTrait, PaginatorTrait,
+    QueryFilter, QueryOrder, QuerySelect, Set,
 };
-use chrono::{Local, NaiveDateTime, TimeDelta};
-use sea_orm::{ActiveModelTrait, Set};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use uuid::Uuid;
 use crate::{
-    entities::visitor,
-    state::AppState,
+    entities::{projects, sea_orm_active_enums::VisitorEventKind, visitor, visitor_event},
+    state::{active_visitor_condition, AppState},
 };
+use super::{password, verify::AdminIdentity};
+
+const DEFAULT_PER_PAGE: u64 = 50;
+const MAX_PER_PAGE: u64 = 200;
+/* how much of the raw log the activity summary includes */
+const TOP_MEDIA: u64 = 20;
+const RECENT_EVENTS: u64 = 50;
+/* keeps the interval added on first open far from overflowing */
+const MAX_ACTIVATE_FOR_SEC: u64 = 10 * 365 * 24 * 60 * 60;
+
+#[derive(thiserror::Error, Debug)]
+pub enum VisitorError {
+    #[error("No visitor uuid({0}) found")]
+    NotFound(String),
+
+    #[error("Passcode can't be empty")]
+    EmptyPasscode,
+
+    #[error("No project id({0}) found")]
+    UnknownProject(i32),
+
+    #[error("valid_for_sec is out of range")]
+    InvalidValidity,
+
+    #[error("activate_for_sec can be at most {MAX_ACTIVATE_FOR_SEC}")]
+    InvalidActivation,
+
+    #[error("{0}")]
+    PasswordError(#[from] password::PasswordError),
+
+    #[error("Db error: {0}")]
+    DbError(#[from] DbErr),
+}
+
+impl IntoResponse for VisitorError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::EmptyPasscode
+            | Self::UnknownProject(_)
+            | Self::InvalidValidity
+            | Self::InvalidActivation => {
+                StatusCode::BAD_REQUEST
+            }
+            Self::PasswordError(_) | Self::DbError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(json!({ "error": self.to_string() }))).into_response()
+    }
+}
+
+/// Limits what a link can see, every field that is set has to match
+#[derive(Serialize, Deserialize, Default)]
+pub struct Scope {
+    pub project_ids: Option<Vec<i32>>,
+    pub country: Option<String>,
+    pub year: Option<i32>,
+}
 
+/// Where the visitor frontend opens, it doesn't restrict anything
+#[derive(Serialize, Deserialize, Default)]
+pub struct Landing {
+    pub project_id: Option<i32>,
+    /* filter preselected in the project list */
+    pub country: Option<String>,
+    pub year: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Limits {
+    /* opens of the link through `/visitor/home/:uuid`, 1 makes it one-time */
+    pub max_page_loads: Option<i32>,
+    /* distinct browsers the link can be opened in */
+    pub max_devices: Option<i32>,
+    /* the link is valid this long after the first open, `valid_for_sec`
+    then only bounds how long it can stay unopened */
+    pub activate_for_sec: Option<u64>,
+}
 
 #[derive(Serialize)]
-pub struct CreateResponse {
-    pub uuid: Uuid,
-    pub valid_till: Option<NaiveDateTime>,
+pub struct VisitorResponse {
+    pub uuid: String,
+    pub valid_till: Option<DateTime<Utc>>,
+    pub label: Option<String>,
+    pub client_name: Option<String>,
+    pub client_company: Option<String>,
+    pub notes: Option<String>,
+    pub created_by: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: Option<DateTime<Utc>>,
+    pub visit_count: i32,
+    pub scope: Scope,
+    pub landing: Landing,
+    pub limits: Limits,
+    pub page_loads: i32,
+    pub devices: usize,
+    pub activated_at: Option<DateTime<Utc>>,
+    pub has_passcode: bool,
+    /* set once revoked or swept after expiring */
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+impl From<visitor::Model> for VisitorResponse {
+    fn from(visitor: visitor::Model) -> Self {
+        Self {
+            uuid: visitor.uuid,
+            valid_till: visitor.time_out,
+            label: visitor.label,
+            client_name: visitor.client_name,
+            client_company: visitor.client_company,
+            notes: visitor.notes,
+            created_by: visitor.created_by,
+            created_at: visitor.created_at,
+            last_seen_at: visitor.last_seen_at,
+            visit_count: visitor.visit_count,
+            scope: Scope {
+                project_ids: visitor.scope_project_ids,
+                country: visitor.scope_country,
+                year: visitor.scope_year,
+            },
+            landing: Landing {
+                project_id: visitor.landing_project_id,
+                country: visitor.default_country,
+                year: visitor.default_year,
+            },
+            limits: Limits {
+                max_page_loads: visitor.max_page_loads,
+                max_devices: visitor.max_devices,
+                activate_for_sec: visitor.activate_for_sec.and_then(|s| u64::try_from(s).ok()),
+            },
+            page_loads: visitor.page_loads,
+            devices: visitor.device_ids.len(),
+            activated_at: visitor.activated_at,
+            has_passcode: visitor.passcode_hash.is_some(),
+            deleted_at: visitor.deleted_at,
+        }
+    }
 }
 
 #[derive(Deserialize)]
 pub struct CreateInfo {
     pub valid_for_sec: Option<u64>,
+    /* who the link is for, only shown to admins */
+    pub label: Option<String>,
+    pub client_name: Option<String>,
+    pub client_company: Option<String>,
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub scope: Scope,
+    #[serde(default)]
+    pub landing: Landing,
+    #[serde(default)]
+    pub limits: Limits,
+    /* asked for before the link opens, stored hashed */
+    pub passcode: Option<String>,
+}
+
+/// `now` plus `valid_for_sec`, which comes straight from the request
+fn valid_till(now: DateTime<Utc>, sec: u64) -> Result<DateTime<Utc>, VisitorError> {
+    i64::try_from(sec)
+        .ok()
+        .and_then(TimeDelta::try_seconds)
+        .and_then(|valid_for| now.checked_add_signed(valid_for))
+        .ok_or(VisitorError::InvalidValidity)
+}
+
+/// `activate_for_sec` as stored, bounded so it can't wrap or overflow the interval
+fn activate_for(sec: Option<u64>) -> Result<Option<i64>, VisitorError> {
+    sec.map(|sec| {
+        i64::try_from(sec)
+            .ok()
+            .filter(|_| sec <= MAX_ACTIVATE_FOR_SEC)
+            .ok_or(VisitorError::InvalidActivation)
+    })
+    .transpose()
+}
+
+async fn check_landing(state: &AppState, landing: &Landing) -> Result<(), VisitorError> {
+    if let Some(id) = landing.project_id {
+        if projects::Entity::find_by_id(id).one(&state.db_conn).await?.is_none() {
+            return Err(VisitorError::UnknownProject(id));
+        }
+    }
+
+    Ok(())
+}
+
+async fn hash_passcode(passcode: String) -> Result<String, VisitorError> {
+    if passcode.is_empty() {
+        return Err(VisitorError::EmptyPasscode);
+    }
+
+    Ok(password::hash(passcode).await?)
 }
 
 pub async fn create(
     State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
     Json(create_info): Json<CreateInfo>,
-) -> impl IntoResponse {
-    let now = Local::now().naive_local();
-    let uuid = Uuid::new_v4();
+) -> Result<Json<VisitorResponse>, VisitorError> {
+    let now = Utc::now();
     let valid_till = create_info
         .valid_for_sec
-        .map(|valid_for| now + TimeDelta::seconds(valid_for as i64));
+        .map(|valid_for| valid_till(now, valid_for))
+        .transpose()?;
+
+    let activate_for_sec = activate_for(create_info.limits.activate_for_sec)?;
+    check_landing(&state, &create_info.landing).await?;
+
+    let passcode_hash = match create_info.passcode {
+        Some(passcode) => Some(hash_passcode(passcode).await?),
+        None => None,
+    };
 
     let visitor = visitor::ActiveModel {
-        uuid: Set(uuid.to_string()),
-        time_out: Set(valid_till.clone()),
+        uuid: Set(Uuid::new_v4().to_string()),
+        time_out: Set(valid_till),
+        label: Set(create_info.label),
+        client_name: Set(create_info.client_name),
+        client_company: Set(create_info.client_company),
+        notes: Set(create_info.notes),
+        created_by: Set(Some(identity.id)),
+        created_at: Set(now),
+        last_seen_at: Set(None),
+        visit_count: Set(0),
+        scope_project_ids: Set(create_info.scope.project_ids),
+        scope_country: Set(create_info.scope.country),
+        scope_year: Set(create_info.scope.year),
+        landing_project_id: Set(create_info.landing.project_id),
+        default_country: Set(create_info.landing.country),
+        default_year: Set(create_info.landing.year),
+        max_page_loads: Set(create_info.limits.max_page_loads),
+        page_loads: Set(0),
+        max_devices: Set(create_info.limits.max_devices),
+        device_ids: Set(vec![]),
+        activate_for_sec: Set(activate_for_sec),
+        activated_at: Set(None),
+        passcode_hash: Set(passcode_hash),
+        deleted_at: Set(None),
+    };
+    let visitor = visitor.insert(&state.db_conn).await?;
+
+    Ok(Json(VisitorResponse::from(visitor)))
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    #[default]
+    All,
+    Active,
+    Expired,
+}
+
+#[derive(Deserialize)]
+pub struct ListQuery {
+    #[serde(default)]
+    pub status: Status,
+    /* starts at 1 */
+    pub page: Option<u64>,
+    pub per_page: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct ListResponse {
+    pub visitors: Vec<VisitorResponse>,
+    pub page: u64,
+    pub per_page: u64,
+    pub total: u64,
+}
+
+pub async fn list(
+    State(state): State<AppState>,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<ListResponse>, VisitorError> {
+    let now = Utc::now();
+    let db_query = match query.status {
+        Status::All => visitor::Entity::find(),
+        Status::Active => visitor::Entity::find().filter(active_visitor_condition(now)),
+        Status::Expired => visitor::Entity::find().filter(active_visitor_condition(now).not()),
+    };
+
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+
+    let paginator = db_query
+        .order_by_desc(visitor::Column::CreatedAt)
+        .paginate(&state.db_conn, per_page);
+    let total = paginator.num_items().await?;
+    let visitors = paginator.fetch_page(page - 1).await?;
+
+    Ok(Json(ListResponse {
+        visitors: visitors.into_iter().map(VisitorResponse::from).collect(),
+        page,
+        per_page,
+        total,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct UpdateInfo {
+    /* counted from now, the link stays valid that long */
+    pub valid_for_sec: Option<u64>,
+    #[serde(default)]
+    pub never_expires: bool,
+    pub label: Option<String>,
+    pub client_name: Option<String>,
+    pub client_company: Option<String>,
+    pub notes: Option<String>,
+    /* replaces the whole scope, `{}` lifts every restriction */
+    pub scope: Option<Scope>,
+    /* replaces the whole landing, like `scope` */
+    pub landing: Option<Landing>,
+    /* replaces every limit, counters are kept */
+    pub limits: Option<Limits>,
+    pub passcode: Option<String>,
+    #[serde(default)]
+    pub remove_passcode: bool,
+}
+
+pub async fn update(
+    State(state): State<AppState>,
+    Path(uuid): Path<String>,
+    Json(info): Json<UpdateInfo>,
+) -> Result<Json<VisitorResponse>, VisitorError> {
+    let existing = visitor::Entity::find_by_id(&uuid)
+        .one(&state.db_conn)
+        .await?
+        .ok_or(VisitorError::NotFound(uuid))?;
+
+    let mut visitor: visitor::ActiveModel = existing.into();
+
+    /* a new expiry brings back a link that already ran out */
+    if info.never_expires {
+        visitor.time_out = Set(None);
+        visitor.deleted_at = Set(None);
+    } else if let Some(valid_for) = info.valid_for_sec {
+        visitor.time_out = Set(Some(valid_till(Utc::now(), valid_for)?));
+        visitor.deleted_at = Set(None);
+    }
+
+    if let Some(label) = info.label {
+        visitor.label = Set(Some(label));
+    }
+
+    if let Some(client_name) = info.client_name {
+        visitor.client_name = Set(Some(client_name));
+    }
+
+    if let Some(client_company) = info.client_company {
+        visitor.client_company = Set(Some(client_company));
+    }
+
+    if let Some(notes) = info.notes {
+        visitor.notes = Set(Some(notes));
+    }
+
+    if let Some(scope) = info.scope {
+        visitor.scope_project_ids = Set(scope.project_ids);
+        visitor.scope_country = Set(scope.country);
+        visitor.scope_year = Set(scope.year);
+    }
+
+    if let Some(landing) = info.landing {
+        check_landing(&state, &landing).await?;
+        visitor.landing_project_id = Set(landing.project_id);
+        visitor.default_country = Set(landing.country);
+        visitor.default_year = Set(landing.year);
+    }
+
+    if let Some(limits) = info.limits {
+        visitor.max_page_loads = Set(limits.max_page_loads);
+        visitor.max_devices = Set(limits.max_devices);
+        visitor.activate_for_sec = Set(activate_for(limits.activate_for_sec)?);
+    }
+
+    if info.remove_passcode {
+        visitor.passcode_hash = Set(None);
+    } else if let Some(passcode) = info.passcode {
+        visitor.passcode_hash = Set(Some(hash_passcode(passcode).await?));
+        /* devices let in with the old passcode have to enter the new one */
+        visitor.device_ids = Set(vec![]);
+    }
+
+    let visitor = visitor.update(&state.db_conn).await?;
+    state.visitor_sessions.invalidate(&visitor.uuid);
+
+    Ok(Json(VisitorResponse::from(visitor)))
+}
+
+/// Takes effect right away on this instance, other instances notice once
+/// their cached copy of the link expires. The link and its activity are
+/// kept for reporting until the sweeper purges them.
+pub async fn revoke(
+    State(state): State<AppState>,
+    Extension(identity): Extension<AdminIdentity>,
+    Path(uuid): Path<String>,
+) -> Result<StatusCode, VisitorError> {
+    let now = Utc::now();
+    let res = visitor::Entity::update_many()
+        .col_expr(visitor::Column::DeletedAt, Expr::value(now))
+        .col_expr(visitor::Column::TimeOut, Expr::value(now))
+        .filter(visitor::Column::Uuid.eq(&uuid))
+        .filter(visitor::Column::DeletedAt.is_null())
+        .exec(&state.db_conn)
+        .await?;
+
+    if res.rows_affected == 0 {
+        return Err(VisitorError::NotFound(uuid));
+    }
+    state.visitor_sessions.invalidate(&uuid);
+
+    tracing::info!("{} revoked visitor {uuid}", identity.email);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Serialize)]
+pub struct MediaViews {
+    pub path: String,
+    pub views: i64,
+}
+
+#[derive(Serialize)]
+pub struct ActivityResponse {
+    pub uuid: String,
+    pub page_opens: i64,
+    pub project_lists: i64,
+    pub media_views: i64,
+    pub first_event_at: Option<DateTime<Utc>>,
+    pub last_event_at: Option<DateTime<Utc>>,
+    pub top_media: Vec<MediaViews>,
+    pub recent: Vec<visitor_event::Model>,
+}
+
+/// What a link was used for, summarized from `visitor_event`
+pub async fn activity(
+    State(state): State<AppState>,
+    Path(uuid): Path<String>,
+) -> Result<Json<ActivityResponse>, VisitorError> {
+    if visitor::Entity::find_by_id(&uuid).one(&state.db_conn).await?.is_none() {
+        return Err(VisitorError::NotFound(uuid));
+    }
+
+    let by_uuid = || {
+        visitor_event::Entity::find().filter(visitor_event::Column::VisitorUuid.eq(&uuid))
     };
-    visitor.insert(&state.db_conn).await.unwrap();
 
-    (StatusCode::OK, Json(CreateResponse { uuid, valid_till }))
+    let counts: Vec<(VisitorEventKind, i64)> = by_uuid()
+        .select_only()
+        .column(visitor_event::Column::Kind)
+        .column_as(visitor_event::Column::Id.count(), "count")
+        .group_by(visitor_event::Column::Kind)
+        .into_tuple()
+        .all(&state.db_conn)
+        .await?;
+    let count_of = |kind| counts.iter().find(|(k, _)| *k == kind).map_or(0, |(_, n)| *n);
+
+    let (first_event_at, last_event_at) = by_uuid()
+        .select_only()
+        .column_as(visitor_event::Column::CreatedAt.min(), "first")
+        .column_as(visitor_event::Column::CreatedAt.max(), "last")
+        .into_tuple::<(Option<DateTime<Utc>>, Option<DateTime<Utc>>)>()
+        .one(&state.db_conn)
+        .await?
+        .unwrap_or_default();
+
+    let top_media = by_uuid()
+        .filter(visitor_event::Column::Kind.eq(VisitorEventKind::Media))
+        .select_only()
+        .column(visitor_event::Column::Path)
+        .column_as(visitor_event::Column::Id.count(), "views")
+        .group_by(visitor_event::Column::Path)
+        .order_by_desc(Expr::col(Alias::new("views")))
+        .limit(TOP_MEDIA)
+        .into_tuple::<(String, i64)>()
+        .all(&state.db_conn)
+        .await?
+        .into_iter()
+        .map(|(path, views)| MediaViews { path, views })
+        .collect();
+
+    let recent = by_uuid()
+        .order_by_desc(visitor_event::Column::Id)
+        .limit(RECENT_EVENTS)
+        .all(&state.db_conn)
+        .await?;
+
+    Ok(Json(ActivityResponse {
+        page_opens: count_of(VisitorEventKind::PageOpen),
+        project_lists: count_of(VisitorEventKind::ProjectList),
+        media_views: count_of(VisitorEventKind::Media),
+        uuid,
+        first_event_at,
+        last_event_at,
+        top_media,
+        recent,
+    }))
 }
@@ -0,0 +1,134 @@
+use super::{filter_projects, ListError, ProjectScope, ProjectsQuery};
+use crate::{entities::projects, state::AppState};
+use axum::{
+    extract::{OriginalUri, Query, State},
+    http::header,
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde_json::{json, Value as JsonValue};
+use std::fmt::Write;
+
+async fn load(
+    state: &AppState,
+    scope: &Option<Extension<ProjectScope>>,
+    query: &ProjectsQuery,
+) -> Result<Vec<projects::Model>, ListError> {
+    let projects = filter_projects(scope, query)?
+        .sorted(query)?
+        .all(&state.db_conn)
+        .await?;
+
+    Ok(projects)
+}
+
+/// Absolute url of the storage router next to the export, admins and
+/// visitors reach it under different prefixes.
+/// Built on `PUBLIC_URL` rather than the request's `Host`, which the client
+/// controls and which misses the scheme behind a proxy
+fn storage_url(public_url: &str, uri: &OriginalUri) -> String {
+    let path = uri.path();
+    let base = &path[..path.rfind('/').map_or(0, |i| i + 1)];
+    format!("{public_url}{base}projects/storage/")
+}
+
+fn cover_url(storage: &str, project: &projects::Model) -> Option<String> {
+    project
+        .pictures
+        .first()
+        .map(|file| format!("{storage}{}", urlencoding::encode(file)))
+}
+
+/// FeatureCollection of the projects `list_projects` would return
+pub async fn geojson(
+    State(state): State<AppState>,
+    scope: Option<Extension<ProjectScope>>,
+    uri: OriginalUri,
+    Query(query): Query<ProjectsQuery>,
+) -> Result<impl IntoResponse, ListError> {
+    let projects = load(&state, &scope, &query).await?;
+    let storage = storage_url(&state.public_url, &uri);
+
+    let features: Vec<JsonValue> = projects
+        .iter()
+        .map(|project| {
+            json!({
+                "type": "Feature",
+                "id": project.id,
+                /* GeoJSON puts longitude first */
+                "geometry": {
+                    "type": "Point",
+                    "coordinates": [project.longitude, project.latitude],
+                },
+                "properties": {
+                    "name": project.name,
+                    "year": project.year,
+                    "country": project.country,
+                    "cover": cover_url(&storage, project),
+                },
+            })
+        })
+        .collect();
+
+    Ok((
+        [(header::CONTENT_TYPE, "application/geo+json")],
+        Json(json!({ "type": "FeatureCollection", "features": features })),
+    ))
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Same projects as `geojson`, as KML placemarks for GIS tools
+pub async fn kml(
+    State(state): State<AppState>,
+    scope: Option<Extension<ProjectScope>>,
+    uri: OriginalUri,
+    Query(query): Query<ProjectsQuery>,
+) -> Result<impl IntoResponse, ListError> {
+    let projects = load(&state, &scope, &query).await?;
+    let storage = storage_url(&state.public_url, &uri);
+
+    let mut kml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <kml xmlns=\"http://www.opengis.net/kml/2.2\">\n\
+        <Document>\n<name>Projects</name>\n",
+    );
+
+    for project in &projects {
+        /* writing into a String can't fail */
+        let _ = write!(
+            kml,
+            "<Placemark id=\"project-{}\">\n\
+            <name>{}</name>\n\
+            <ExtendedData>\n\
+            <Data name=\"year\"><value>{}</value></Data>\n\
+            <Data name=\"country\"><value>{}</value></Data>\n",
+            project.id,
+            escape_xml(&project.name),
+            project.year,
+            escape_xml(&project.country),
+        );
+
+        if let Some(cover) = cover_url(&storage, project) {
+            let _ = writeln!(kml, "<Data name=\"cover\"><value>{}</value></Data>", escape_xml(&cover));
+        }
+
+        let _ = write!(
+            kml,
+            "</ExtendedData>\n\
+            <Point><coordinates>{},{}</coordinates></Point>\n\
+            </Placemark>\n",
+            project.longitude, project.latitude,
+        );
+    }
+
+    kml.push_str("</Document>\n</kml>\n");
+
+    Ok(([(header::CONTENT_TYPE, "application/vnd.google-earth.kml+xml")], kml))
+}
@@ -0,0 +1,721 @@
+use crate::{entities::{project_tag, projects, tag, visitor}, state::AppState};
+use axum::{
+    body::Body, extract::{Query, State}, http::{Request, StatusCode}, middleware::{self, Next}, response::{IntoResponse, Response}, routing, Extension, Json
+};
+use sea_orm::{
+    sea_query::{Expr, Query as SubQuery, SimpleExpr}, ColumnTrait, Condition, DbErr,
+    EntityTrait, FromQueryResult, Order, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect,
+    QueryTrait, Select,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use std::{collections::BTreeSet, str::FromStr};
+use tower_http::services::ServeDir;
+
+mod export;
+
+const ALLOWED_FILE_EXT: [&'static str; 8] = [
+    "jpg",
+    "jpeg",
+    "png",
+    "heic",
+    "html",
+    "css",
+    "js", 
+    "mp4"
+];
+
+async fn filter_file_ext(req: Request<Body>, next: Next) -> impl IntoResponse {
+    let req_path = req.uri().path();
+    let is_allowed = ALLOWED_FILE_EXT.iter().any(|ext| req_path.ends_with(ext));
+    if is_allowed {
+        next.run(req).await
+    } else {
+        (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE, 
+            Json(json!({"allowed_ext": ALLOWED_FILE_EXT}))
+        )
+            .into_response()
+    }
+}
+
+/// Projects a visitor link is limited to, inserted as a request extension by
+/// the visitor middleware. Admin requests don't carry one and see everything.
+#[derive(Clone, Debug, Default)]
+pub struct ProjectScope {
+    pub project_ids: Option<Vec<i32>>,
+    pub country: Option<String>,
+    pub year: Option<i32>,
+}
+
+impl From<&visitor::Model> for ProjectScope {
+    fn from(visitor: &visitor::Model) -> Self {
+        Self {
+            project_ids: visitor.scope_project_ids.clone(),
+            country: visitor.scope_country.clone(),
+            year: visitor.scope_year,
+        }
+    }
+}
+
+impl ProjectScope {
+    pub fn apply(&self, query: Select<projects::Entity>) -> Select<projects::Entity> {
+        let mut cond = Condition::all();
+
+        if let Some(ids) = &self.project_ids {
+            cond = cond.add(projects::Column::Id.is_in(ids.iter().copied()));
+        }
+
+        if let Some(country) = &self.country {
+            cond = cond.add(projects::Column::Country.eq(country));
+        }
+
+        if let Some(year) = self.year {
+            cond = cond.add(projects::Column::Year.eq(year));
+        }
+
+        query.filter(cond)
+    }
+}
+
+fn scoped(scope: &Option<Extension<ProjectScope>>) -> Select<projects::Entity> {
+    match scope {
+        Some(Extension(scope)) => scope.apply(projects::Entity::find()),
+        None => projects::Entity::find(),
+    }
+}
+
+/// Visitors only get files attached to a project they can see
+async fn filter_storage_scope(
+    State(state): State<AppState>,
+    scope: Option<Extension<ProjectScope>>,
+    req: Request<Body>,
+    next: Next,
+) -> impl IntoResponse {
+    if scope.is_none() {
+        return next.run(req).await;
+    }
+
+    let file_name = req.uri().path().trim_start_matches('/');
+    let file_name = urlencoding::decode(file_name)
+        .map(|f| f.into_owned())
+        .unwrap_or_default();
+
+    let visible = scoped(&scope)
+        .filter(Expr::cust_with_values(
+            "($1 = ANY(\"pictures\") OR $1 = ANY(\"videos\"))",
+            [file_name],
+        ))
+        .count(&state.db_conn)
+        .await;
+
+    match visible {
+        Ok(n) if n > 0 => next.run(req).await,
+        Ok(_) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            tracing::error!("DataBase Error: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+const MAX_PER_PAGE: u64 = 200;
+const EARTH_RADIUS_KM: f64 = 6371.0;
+const KM_PER_DEGREE: f64 = EARTH_RADIUS_KM * std::f64::consts::PI / 180.0;
+
+#[derive(thiserror::Error, Debug)]
+enum ListError {
+    #[error("Unknown field: {0}")]
+    UnknownField(String),
+
+    #[error("Invalid `{0}`, expected {1}")]
+    InvalidParam(&'static str, &'static str),
+
+    #[error("Sorting by distance and `radius_km` need a `near` point")]
+    MissingPoint,
+
+    #[error("Sorting by relevance needs a search `q`")]
+    MissingSearch,
+
+    #[error("Db error: {0}")]
+    DbError(#[from] DbErr),
+}
+
+impl IntoResponse for ListError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Self::UnknownField(_)
+            | Self::InvalidParam(..)
+            | Self::MissingPoint
+            | Self::MissingSearch => StatusCode::BAD_REQUEST,
+            Self::DbError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(json!({ "error": self.to_string() }))).into_response()
+    }
+}
+
+/// Great-circle distance in km from (`lat`, `lng`) to a project's location
+fn distance_km(lat: f64, lng: f64) -> SimpleExpr {
+    Expr::cust_with_values(
+        "(2 * $3 * ASIN(SQRT(POWER(SIN(RADIANS(\"latitude\" - $1) / 2), 2) \
+        + COS(RADIANS($1)) * COS(RADIANS(\"latitude\")) \
+        * POWER(SIN(RADIANS(\"longitude\" - $2) / 2), 2))))",
+        [lat, lng, EARTH_RADIUS_KM],
+    )
+}
+
+/// Comma separated numbers, `None` unless there are exactly `N` finite ones
+fn parse_coords<const N: usize>(s: &str) -> Option<[f64; N]> {
+    let values: Vec<f64> = s
+        .split(',')
+        .map(|v| v.trim().parse::<f64>().ok().filter(|v| v.is_finite()))
+        .collect::<Option<_>>()?;
+
+    values.try_into().ok()
+}
+
+fn valid_lat(lat: f64) -> bool {
+    (-90.0..=90.0).contains(&lat)
+}
+
+fn valid_lng(lng: f64) -> bool {
+    (-180.0..=180.0).contains(&lng)
+}
+
+/// `lat,lng`
+fn parse_near(near: &str) -> Result<(f64, f64), ListError> {
+    match parse_coords::<2>(near) {
+        Some([lat, lng]) if valid_lat(lat) && valid_lng(lng) => Ok((lat, lng)),
+        _ => Err(ListError::InvalidParam("near", "`lat,lng`")),
+    }
+}
+
+/// `min_lng,min_lat,max_lng,max_lat` like GeoJSON, `min_lng` is greater
+/// than `max_lng` for boxes crossing the antimeridian
+fn parse_bbox(bbox: &str) -> Result<Condition, ListError> {
+    match parse_coords::<4>(bbox) {
+        Some([min_lng, min_lat, max_lng, max_lat])
+            if valid_lat(min_lat)
+                && valid_lat(max_lat)
+                && min_lat <= max_lat
+                && valid_lng(min_lng)
+                && valid_lng(max_lng) =>
+        {
+            Ok(Condition::all()
+                .add(projects::Column::Latitude.between(min_lat, max_lat))
+                .add(longitude_between(min_lng, max_lng)))
+        }
+        _ => Err(ListError::InvalidParam("bbox", "`min_lng,min_lat,max_lng,max_lat`")),
+    }
+}
+
+fn longitude_between(min: f64, max: f64) -> Condition {
+    if min <= max {
+        Condition::all().add(projects::Column::Longitude.between(min, max))
+    } else {
+        Condition::any()
+            .add(projects::Column::Longitude.gte(min))
+            .add(projects::Column::Longitude.lte(max))
+    }
+}
+
+/// Projects within `radius_km` of (`lat`, `lng`). The box around the circle
+/// lets postgres use the location index before the exact distance is checked.
+fn within_radius(lat: f64, lng: f64, radius_km: f64) -> Condition {
+    let d_lat = radius_km / KM_PER_DEGREE;
+    let mut cond = Condition::all()
+        .add(projects::Column::Latitude.between(lat - d_lat, lat + d_lat))
+        .add(Expr::expr(distance_km(lat, lng)).lte(radius_km));
+
+    /* near the poles or for huge radii every longitude is in range */
+    let d_lng = d_lat / lat.to_radians().cos();
+    if lat.abs() + d_lat < 90.0 && d_lng < 180.0 {
+        let wrap = |lng: f64| (lng + 540.0) % 360.0 - 180.0;
+        cond = cond.add(longitude_between(wrap(lng - d_lng), wrap(lng + d_lng)));
+    }
+
+    cond
+}
+
+/// Turns free text into a prefix `tsquery` where every word has to match,
+/// `villa po` becomes `villa:* & po:*`. Only letters and digits are kept so
+/// user input can't inject tsquery operators.
+fn search_terms(q: &str) -> Option<String> {
+    let terms: Vec<String> = q
+        .split_whitespace()
+        .map(|word| word.chars().filter(|c| c.is_alphanumeric()).collect::<String>())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("{word}:*"))
+        .collect();
+
+    (!terms.is_empty()).then(|| terms.join(" & "))
+}
+
+/* `search` is generated from name, country and description (see the
+   project_search migration) and covered by a GIN index */
+fn search_matches(terms: &str) -> SimpleExpr {
+    Expr::cust_with_values("\"search\" @@ to_tsquery('simple', $1)", [terms])
+}
+
+fn search_rank(terms: &str) -> SimpleExpr {
+    Expr::cust_with_values("ts_rank(\"search\", to_tsquery('simple', $1))", [terms])
+}
+
+/// Matching parts of the description wrapped in `<mark>`, the rest is
+/// HTML-escaped so the snippet can be rendered as is
+fn search_snippet(terms: &str) -> SimpleExpr {
+    /* escaped before ts_headline, afterwards the marks couldn't be told apart */
+    Expr::cust_with_values(
+        "ts_headline('simple', \
+        replace(replace(replace(replace(replace(\"description\", \
+        '&', '&amp;'), '<', '&lt;'), '>', '&gt;'), '\"', '&quot;'), '''', '&#39;'), \
+        to_tsquery('simple', $1), \
+        'StartSel=<mark>, StopSel=</mark>, MaxFragments=2, MaxWords=20, MinWords=8')",
+        [terms],
+    )
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum SortBy {
+    Year,
+    Name,
+    Id,
+    Distance,
+    Relevance,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl From<SortOrder> for Order {
+    fn from(order: SortOrder) -> Self {
+        match order {
+            SortOrder::Asc => Order::Asc,
+            SortOrder::Desc => Order::Desc,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ProjectsQuery {
+    pub country: Option<String>,
+    pub year: Option<u64>,
+    /* full-text search, adds `rank` and `snippet` to every project */
+    pub q: Option<String>,
+    /* starts at 1, without `page` or `per_page` every project is returned */
+    pub page: Option<u64>,
+    pub per_page: Option<u64>,
+    /* best match first when searching, newest first otherwise */
+    pub sort: Option<SortBy>,
+    pub order: Option<SortOrder>,
+    /* comma separated columns, e.g. `id,name,year` */
+    pub fields: Option<String>,
+    /* `lat,lng` to measure from, adds `distance_km` to every project */
+    pub near: Option<String>,
+    /* only projects this close to `near` */
+    pub radius_km: Option<f64>,
+    /* only projects on the map view, `min_lng,min_lat,max_lng,max_lat` */
+    pub bbox: Option<String>,
+    /* comma separated tag slugs, projects need all of them */
+    pub tag: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ProjectsResponse {
+    projects: Vec<JsonValue>,
+    total: u64,
+    page: u64,
+    per_page: Option<u64>,
+}
+
+/// Columns to select and whether `tags` was asked for, it isn't a column.
+/// Without any column (`fields=` or `fields=tags`) only the id is selected
+fn parse_fields(fields: &str) -> Result<(Vec<projects::Column>, bool), ListError> {
+    let mut with_tags = false;
+    let mut columns: Vec<projects::Column> = fields
+        .split(',')
+        .map(str::trim)
+        .filter(|f| !f.is_empty())
+        .filter(|f| {
+            with_tags |= *f == "tags";
+            *f != "tags"
+        })
+        .map(|f| projects::Column::from_str(f).map_err(|_| ListError::UnknownField(f.to_owned())))
+        .collect::<Result<_, _>>()?;
+
+    /* a SELECT without columns is a syntax error */
+    if columns.is_empty() {
+        columns.push(projects::Column::Id);
+    }
+
+    Ok((columns, with_tags))
+}
+
+/// Projects carrying every one of `slugs`, which have to be distinct for the
+/// count to match
+fn tagged_with(slugs: BTreeSet<String>) -> SimpleExpr {
+    let count = slugs.len() as i64;
+    let ids = SubQuery::select()
+        .column((project_tag::Entity, project_tag::Column::ProjectId))
+        .from(project_tag::Entity)
+        .inner_join(
+            tag::Entity,
+            Expr::col((tag::Entity, tag::Column::Id))
+                .equals((project_tag::Entity, project_tag::Column::TagId)),
+        )
+        .and_where(Expr::col((tag::Entity, tag::Column::Slug)).is_in(slugs))
+        .group_by_col((project_tag::Entity, project_tag::Column::ProjectId))
+        .and_having(Expr::col((tag::Entity, tag::Column::Id)).count_distinct().eq(count))
+        .to_owned();
+
+    projects::Column::Id.in_subquery(ids)
+}
+
+/// Slugs of the project's tags as an array
+fn tag_slugs() -> SimpleExpr {
+    Expr::cust(
+        "ARRAY(SELECT \"tag\".\"slug\" FROM \"tag\" \
+        JOIN \"project_tag\" ON \"project_tag\".\"tag_id\" = \"tag\".\"id\" \
+        WHERE \"project_tag\".\"project_id\" = \"projects\".\"id\" \
+        ORDER BY \"tag\".\"slug\")",
+    )
+}
+
+/// Projects matching the filters of a `ProjectsQuery`, shared by the
+/// listing and the map exports
+struct Filtered {
+    select: Select<projects::Entity>,
+    point: Option<(f64, f64)>,
+    terms: Option<String>,
+}
+
+fn filter_projects(
+    scope: &Option<Extension<ProjectScope>>,
+    query: &ProjectsQuery,
+) -> Result<Filtered, ListError> {
+    let db_query = if let Some(year) = query.year {
+        scoped(scope)
+            .filter(projects::Column::Year.eq(year))
+    } else {
+        scoped(scope)
+    };
+
+    let db_query = if let Some(country) = &query.country {
+        db_query.filter(projects::Column::Country.eq(country))
+    } else {
+        db_query
+    };
+
+    let terms = query.q.as_deref().and_then(search_terms);
+    let db_query = match terms.as_deref() {
+        Some(terms) => db_query.filter(search_matches(terms)),
+        None => db_query,
+    };
+
+    let point = query.near.as_deref().map(parse_near).transpose()?;
+
+    let db_query = match query.radius_km {
+        Some(radius_km) if radius_km.is_finite() && radius_km > 0.0 => {
+            let (lat, lng) = point.ok_or(ListError::MissingPoint)?;
+            db_query.filter(within_radius(lat, lng, radius_km))
+        }
+        Some(_) => return Err(ListError::InvalidParam("radius_km", "a positive number")),
+        None => db_query,
+    };
+
+    let db_query = match query.bbox.as_deref() {
+        Some(bbox) => db_query.filter(parse_bbox(bbox)?),
+        None => db_query,
+    };
+
+    /* `?tag=a,a` must not ask for two matches */
+    let slugs: BTreeSet<String> = query
+        .tag
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(|slug| slug.trim().to_owned())
+        .filter(|slug| !slug.is_empty())
+        .collect();
+
+    let db_query = if slugs.is_empty() {
+        db_query
+    } else {
+        db_query.filter(tagged_with(slugs))
+    };
+
+    Ok(Filtered { select: db_query, point, terms })
+}
+
+impl Filtered {
+    fn sorted(self, query: &ProjectsQuery) -> Result<Select<projects::Entity>, ListError> {
+        let Filtered { select: db_query, point, terms } = self;
+
+        let sort = query.sort.unwrap_or(if terms.is_some() { SortBy::Relevance } else { SortBy::Year });
+        let order: Order = query
+            .order
+            .unwrap_or(match sort {
+                SortBy::Year | SortBy::Relevance => SortOrder::Desc,
+                _ => SortOrder::Asc,
+            })
+            .into();
+
+        /* ties broken by id so pages don't overlap */
+        let id_order = if sort == SortBy::Id { order.clone() } else { Order::Asc };
+
+        let db_query = match sort {
+            SortBy::Year => db_query.order_by(projects::Column::Year, order),
+            SortBy::Name => db_query.order_by(projects::Column::Name, order),
+            SortBy::Id => db_query,
+            SortBy::Distance => {
+                let (lat, lng) = point.ok_or(ListError::MissingPoint)?;
+                db_query.order_by(distance_km(lat, lng), order)
+            }
+            SortBy::Relevance => {
+                let terms = terms.as_deref().ok_or(ListError::MissingSearch)?;
+                db_query.order_by(search_rank(terms), order)
+            }
+        };
+
+        Ok(db_query.order_by(projects::Column::Id, id_order))
+    }
+}
+
+async fn list_projects(
+    State(state): State<AppState>,
+    scope: Option<Extension<ProjectScope>>,
+    Query(query): Query<ProjectsQuery>,
+) -> Result<Json<ProjectsResponse>, ListError> {
+    let paged = query.page.is_some() || query.per_page.is_some();
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = paged.then(|| query.per_page.unwrap_or(MAX_PER_PAGE).clamp(1, MAX_PER_PAGE));
+
+    /* Postgres takes a bigint offset */
+    let offset = (page - 1)
+        .checked_mul(per_page.unwrap_or(0))
+        .filter(|offset| i64::try_from(*offset).is_ok())
+        .ok_or(ListError::InvalidParam("page", "a smaller page number"))?;
+
+    let filtered = filter_projects(&scope, &query)?;
+    let total = filtered.select.clone().count(&state.db_conn).await?;
+
+    let point = filtered.point;
+    let terms = filtered.terms.clone();
+    let db_query = filtered.sorted(&query)?;
+
+    let (db_query, with_tags) = match query.fields.as_deref() {
+        Some(fields) => {
+            let (columns, with_tags) = parse_fields(fields)?;
+            (db_query.select_only().columns(columns), with_tags)
+        }
+        None => (db_query, true),
+    };
+
+    let db_query = if with_tags {
+        db_query.column_as(tag_slugs(), "tags")
+    } else {
+        db_query
+    };
+
+    let db_query = match point {
+        Some((lat, lng)) => db_query.column_as(distance_km(lat, lng), "distance_km"),
+        None => db_query,
+    };
+
+    let db_query = match terms.as_deref() {
+        Some(terms) => db_query
+            .column_as(search_rank(terms), "rank")
+            .column_as(search_snippet(terms), "snippet"),
+        None => db_query,
+    };
+
+    let db_query = match per_page {
+        Some(per_page) => db_query.offset(offset).limit(per_page),
+        None => db_query,
+    };
+
+    let projects = db_query
+        .into_json()
+        .all(&state.db_conn)
+        .await?;
+
+    Ok(Json(ProjectsResponse { projects, total, page, per_page }))
+}
+
+#[derive(FromQueryResult, Serialize)]
+struct TagFacet {
+    id: i32,
+    slug: String,
+    name: String,
+    count: i64,
+}
+
+#[derive(FromQueryResult, Serialize)]
+struct CountryFacet {
+    country: String,
+    count: i64,
+}
+
+#[derive(FromQueryResult, Serialize)]
+struct YearFacet {
+    year: i32,
+    count: i64,
+}
+
+#[derive(Serialize)]
+struct FacetsResponse {
+    total: u64,
+    tags: Vec<TagFacet>,
+    countries: Vec<CountryFacet>,
+    years: Vec<YearFacet>,
+}
+
+/// Project counts per tag, country and year for the same filters
+/// `list_projects` takes, sorting and paging are ignored
+async fn facets(
+    State(state): State<AppState>,
+    scope: Option<Extension<ProjectScope>>,
+    Query(query): Query<ProjectsQuery>,
+) -> Result<Json<FacetsResponse>, ListError> {
+    let db_query = filter_projects(&scope, &query)?.select;
+
+    let total = db_query.clone().count(&state.db_conn).await?;
+
+    let ids = db_query
+        .clone()
+        .select_only()
+        .column(projects::Column::Id)
+        .into_query();
+
+    let tags = tag::Entity::find()
+        .select_only()
+        .columns([tag::Column::Id, tag::Column::Slug, tag::Column::Name])
+        .column_as(Expr::col((project_tag::Entity, project_tag::Column::ProjectId)).count(), "count")
+        .inner_join(project_tag::Entity)
+        .filter(project_tag::Column::ProjectId.in_subquery(ids))
+        .group_by(tag::Column::Id)
+        .order_by_asc(tag::Column::Name)
+        .into_model::<TagFacet>()
+        .all(&state.db_conn)
+        .await?;
+
+    let countries = db_query
+        .clone()
+        .select_only()
+        .column(projects::Column::Country)
+        .column_as(Expr::col(projects::Column::Id).count(), "count")
+        .group_by(projects::Column::Country)
+        .order_by_asc(projects::Column::Country) /* alphabetically */
+        .into_model::<CountryFacet>()
+        .all(&state.db_conn)
+        .await?;
+
+    let years = db_query
+        .select_only()
+        .column(projects::Column::Year)
+        .column_as(Expr::col(projects::Column::Id).count(), "count")
+        .group_by(projects::Column::Year)
+        .order_by_desc(projects::Column::Year)
+        .into_model::<YearFacet>()
+        .all(&state.db_conn)
+        .await?;
+
+    Ok(Json(FacetsResponse { total, tags, countries, years }))
+}
+
+#[derive(Deserialize)]
+struct YearsQuery {
+    pub country: Option<String>,
+}
+
+#[derive(Serialize)]
+struct YearsResponse {
+    pub years: Vec<i32>,
+}
+
+async fn list_years(
+    State(state): State<AppState>,
+    scope: Option<Extension<ProjectScope>>,
+    Query(q): Query<YearsQuery>,
+) -> impl IntoResponse {
+    let db_query = if let Some(country) = q.country {
+        scoped(&scope).filter(projects::Column::Country.eq(&country))
+    } else {
+        scoped(&scope)
+    };
+
+    let years: Vec<i32> = db_query
+        .select_only()
+        .column(projects::Column::Year)
+        .order_by_desc(projects::Column::Year)
+        .distinct()
+        .into_tuple()
+        .all(&state.db_conn)
+        .await
+        .unwrap();
+
+    Json(YearsResponse { years })
+}
+
+#[derive(Deserialize)]
+struct CountriesQuery {
+    pub year: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct CountriesResponse {
+    pub countries: Vec<String>,
+}
+
+async fn list_countries(
+    State(state): State<AppState>,
+    scope: Option<Extension<ProjectScope>>,
+    Query(q): Query<CountriesQuery>,
+) -> impl IntoResponse {
+    let db_query = if let Some(year) = q.year {
+        scoped(&scope).filter(projects::Column::Year.eq(year))
+    } else {
+        scoped(&scope)
+    };
+
+    let countries: Vec<String> = db_query
+        .select_only()
+        .column(projects::Column::Country)
+        .order_by_asc(projects::Column::Country) /* alphabetically */
+        .distinct()
+        .into_tuple()
+        .all(&state.db_conn)
+        .await
+        .unwrap();
+
+    Json(CountriesResponse { countries })
+}
+
+/// NOTE: verification should be done on higher level, visitors must also
+/// carry their `ProjectScope`
+pub fn get_router(state: AppState) -> axum::Router<AppState> {
+    let static_router = axum::Router::new()
+        .nest_service("/", ServeDir::new("storage"))
+        .layer(middleware::from_fn_with_state(state, filter_storage_scope))
+        .layer(middleware::from_fn(filter_file_ext));
+
+    axum::Router::new()
+        .route("/", routing::get(list_projects))
+        .route("/years", routing::get(list_years))
+        .route("/countries", routing::get(list_countries))
+        .route("/facets", routing::get(facets))
+        .nest("/storage", static_router)
+}
+
+/// `/projects.geojson` and `/projects.kml`, merged next to the `/projects`
+/// nest since they share its query parameters but not its prefix
+pub fn get_export_router() -> axum::Router<AppState> {
+    axum::Router::new()
+        .route("/projects.geojson", routing::get(export::geojson))
+        .route("/projects.kml", routing::get(export::kml))
+}
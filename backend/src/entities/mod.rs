@@ -2,6 +2,16 @@
 
 pub mod prelude;
 
+pub mod invite;
+pub mod jwt_key;
+pub mod login_attempt;
+pub mod password_reset;
+pub mod project_tag;
 pub mod projects;
+pub mod refresh_token;
+pub mod sea_orm_active_enums;
+pub mod spent_refresh_token;
+pub mod tag;
 pub mod user;
 pub mod visitor;
+pub mod visitor_event;
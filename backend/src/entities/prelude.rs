@@ -1,5 +1,14 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.15
 
+pub use super::invite::Entity as Invite;
+pub use super::jwt_key::Entity as JwtKey;
+pub use super::login_attempt::Entity as LoginAttempt;
+pub use super::password_reset::Entity as PasswordReset;
+pub use super::project_tag::Entity as ProjectTag;
 pub use super::projects::Entity as Projects;
+pub use super::refresh_token::Entity as RefreshToken;
+pub use super::spent_refresh_token::Entity as SpentRefreshToken;
+pub use super::tag::Entity as Tag;
 pub use super::user::Entity as User;
 pub use super::visitor::Entity as Visitor;
+pub use super::visitor_event::Entity as VisitorEvent;
@@ -24,6 +24,24 @@ pub struct Model {
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(has_many = "super::project_tag::Entity")]
+    ProjectTag,
+}
+
+impl Related<super::project_tag::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ProjectTag.def()
+    }
+}
+
+impl Related<super::tag::Entity> for Entity {
+    fn to() -> RelationDef {
+        super::project_tag::Relation::Tag.def()
+    }
+    fn via() -> Option<RelationDef> {
+        Some(super::project_tag::Relation::Projects.def().rev())
+    }
+}
 
 impl ActiveModelBehavior for ActiveModel {}
@@ -1,5 +1,6 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.15
 
+use super::sea_orm_active_enums::Role;
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -12,9 +13,32 @@ pub struct Model {
     pub last_name: Option<String>,
     pub email: String,
     pub password: String,
+    pub role: Role,
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+    pub totp_recovery_codes: Vec<String>,
+    pub totp_last_step: Option<i64>,
+    pub disabled: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(has_many = "super::password_reset::Entity")]
+    PasswordReset,
+    #[sea_orm(has_many = "super::refresh_token::Entity")]
+    RefreshToken,
+}
+
+impl Related<super::password_reset::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PasswordReset.def()
+    }
+}
+
+impl Related<super::refresh_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RefreshToken.def()
+    }
+}
 
 impl ActiveModelBehavior for ActiveModel {}
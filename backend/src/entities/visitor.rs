@@ -8,10 +8,45 @@ use serde::{Deserialize, Serialize};
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub uuid: String,
-    pub time_out: Option<DateTime>,
+    pub time_out: Option<DateTimeUtc>,
+    pub label: Option<String>,
+    pub client_name: Option<String>,
+    pub client_company: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub notes: Option<String>,
+    pub created_by: Option<i32>,
+    pub created_at: DateTimeUtc,
+    pub last_seen_at: Option<DateTimeUtc>,
+    pub visit_count: i32,
+    pub scope_project_ids: Option<Vec<i32>>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub scope_country: Option<String>,
+    pub scope_year: Option<i32>,
+    pub max_page_loads: Option<i32>,
+    pub page_loads: i32,
+    pub max_devices: Option<i32>,
+    pub device_ids: Vec<String>,
+    pub activate_for_sec: Option<i64>,
+    pub activated_at: Option<DateTimeUtc>,
+    #[serde(skip_serializing)]
+    pub passcode_hash: Option<String>,
+    pub deleted_at: Option<DateTimeUtc>,
+    pub landing_project_id: Option<i32>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub default_country: Option<String>,
+    pub default_year: Option<i32>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(has_many = "super::visitor_event::Entity")]
+    VisitorEvent,
+}
+
+impl Related<super::visitor_event::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::VisitorEvent.def()
+    }
+}
 
 impl ActiveModelBehavior for ActiveModel {}
@@ -6,27 +6,40 @@ pub mod admin;
 pub mod entities;
 pub mod state;
 pub mod common;
-mod visitor;
+pub mod mailer;
+pub mod visitor;
 
 
-pub async fn create_routes(admin_dir: String, visitor_dir: String) -> anyhow::Result<Router> {
+/// The app together with the visitor sweeper, which should be stopped once
+/// the server has shut down
+pub async fn create_routes(
+    admin_dir: String,
+    visitor_dir: String,
+) -> anyhow::Result<(Router, visitor::SweeperHandle)> {
     let state = AppState::init(admin_dir, visitor_dir).await?;
+    let sweeper = visitor::Sweeper::from_env(state.db_conn.clone()).spawn();
 
-    Ok(Router::new()
+    let router = Router::new()
         .nest("/admin", Router::new()
             .nest("/", admin::page_router(state.clone())) /* get actuall html for admin page */
-            .nest("/api", admin::api_router()) /* everything that needs verification */
-            .route("/auth", routing::post(admin::auth))) /* auth endpoint */
+            .nest("/api", admin::api_router(state.clone())) /* everything that needs verification */
+            .route("/auth", routing::post(admin::auth)) /* auth endpoint */
+            .route("/auth/totp", routing::post(admin::verify_totp)) /* second factor */
+            .route("/register", routing::post(admin::register)) /* redeems an invite */
+            .route("/password-reset", routing::post(admin::request_password_reset))
+            .route("/password-reset/confirm", routing::post(admin::confirm_password_reset))
+            .route("/refresh", routing::post(admin::refresh))
+            .route("/logout", routing::post(admin::logout)))
 
         .nest("/visitor", Router::new()
-            .route("/home/:visitor_uuid", routing::get(visitor::page))
+            .route("/home/:visitor_uuid", routing::get(visitor::page).post(visitor::submit_passcode))
             .nest("/api", visitor::api_router(state.clone()))
             .nest("/", visitor::static_router(state.clone())))
 
         .with_state(state)
         .layer(CorsLayer::very_permissive())
         .layer(DefaultBodyLimit::max(100 * 1024 * 1024)) // 100mb
-        .layer(RequestBodyLimitLayer::new(100 * 1024 * 1024))
-    )
+        .layer(RequestBodyLimitLayer::new(100 * 1024 * 1024));
 
+    Ok((router, sweeper))
 }
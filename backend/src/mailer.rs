@@ -0,0 +1,82 @@
+use async_trait::async_trait;
+use std::{env, path::PathBuf, sync::Arc};
+use tokio::{fs, io::{self, AsyncWriteExt}};
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MailError {
+    #[error("Failed to write mail: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Failed to send mail: {0}")]
+    Transport(String),
+}
+
+/// Outgoing mail, swap the implementation to plug in a real provider
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, mail: Mail) -> Result<(), MailError>;
+}
+
+/// Only logs that a mail was sent, never its body, which may carry tokens
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, mail: Mail) -> Result<(), MailError> {
+        tracing::info!("Mail to {} | {} (body not logged)", mail.to, mail.subject);
+        Ok(())
+    }
+}
+
+/// Drops every mail as a file into a directory, usable offline
+pub struct FileMailer {
+    dir: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, mail: Mail) -> Result<(), MailError> {
+        fs::create_dir_all(&self.dir).await?;
+        let path = self.dir.join(format!(
+            "{}_{}.eml",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S"),
+            Uuid::new_v4()
+        ));
+        let contents = format!("To: {}\nSubject: {}\n\n{}\n", mail.to, mail.subject, mail.body);
+        /* mails carry reset links, only the server's user can read them */
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+            .await?;
+        file.write_all(contents.as_bytes()).await?;
+        tracing::info!("Mail to {} written to {}", mail.to, path.display());
+        Ok(())
+    }
+}
+
+/// [`FileMailer`] into `MAIL_OUTBOX_DIR` (`outbox` by default), `MAILER=log`
+/// opts into the [`LogMailer`] where mails can't be delivered at all
+pub fn from_env() -> Arc<dyn Mailer> {
+    if env::var("MAILER").is_ok_and(|mailer| mailer == "log") {
+        tracing::warn!("MAILER=log, mails are dropped and password resets won't arrive");
+        return Arc::new(LogMailer);
+    }
+
+    Arc::new(FileMailer::new(env::var("MAIL_OUTBOX_DIR").unwrap_or_else(|_| "outbox".into())))
+}
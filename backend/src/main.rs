@@ -119,13 +119,17 @@ async fn main() -> anyhow::Result<()> {
     )
     .await?;
 
-    let app = create_routes(admin, visitor).await?;
+    let (app, sweeper) = create_routes(admin, visitor).await?;
     let addr = SocketAddr::from(([0, 0, 0, 0], ports.https));
     tracing::debug!("listening on {addr}");
-    axum_server::bind_rustls(addr, config)
+    let served = axum_server::bind_rustls(addr, config)
         .handle(handle)
-        .serve(app.into_make_service())
-        .await?;
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>()) /* client ip for login throttling */
+        .await;
 
-    Ok(())
+    /* `handle` has drained the connections by now, background jobs go last,
+       also when serving failed */
+    sweeper.stop().await;
+
+    Ok(served?)
 }
@@ -1,21 +1,50 @@
-use crate::entities::visitor;
-use lazy_static::lazy_static;
-use rand::{distributions::Alphanumeric, Rng};
-use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, PaginatorTrait, QueryFilter};
-use std::{env, sync::Arc, time::Duration};
-
-lazy_static! {
-    /// NOTE: regenerated after each server restart
-    pub static ref SECRET_KEY: String = rand::thread_rng()
-        .sample_iter(&Alphanumeric)
-        .take(32)
-        .map(char::from)
-        .collect();
+use crate::{
+    admin::{invite, password, KeyPurpose, KeyStore, Throttle},
+    entities::visitor,
+    mailer::{self, Mailer},
+    visitor::VisitorSessions,
+};
+use sea_orm::{
+    sea_query::Expr, ColumnTrait, Condition, DatabaseConnection, DbErr, EntityTrait,
+    QueryFilter,
+};
+use std::{
+    env,
+    fs::{OpenOptions, Permissions},
+    io::{self, IsTerminal, Write},
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+
+/* only meant for local development, links in mails and exports need the real one */
+const DEV_PUBLIC_URL: &str = "https://localhost:8000";
+
+/* requests closer together than this belong to the same visit */
+const VISIT_IDLE_GAP: chrono::TimeDelta = chrono::TimeDelta::minutes(30);
+
+/// Links without a `time_out` never expire, revoked and swept ones are
+/// only kept for reporting
+pub(crate) fn active_visitor_condition(now: chrono::DateTime<chrono::Utc>) -> Condition {
+    Condition::all()
+        .add(visitor::Column::DeletedAt.is_null())
+        .add(
+            Condition::any()
+                .add(visitor::Column::TimeOut.is_null())
+                .add(visitor::Column::TimeOut.gte(now)),
+        )
 }
 
 #[derive(Clone)]
 pub struct AppState {
     pub db_conn: DatabaseConnection,
+    pub keys: KeyStore,
+    pub throttle: Throttle,
+    pub mailer: Arc<dyn Mailer>,
+    pub visitor_sessions: VisitorSessions,
+    /* where clients reach the server, without a trailing slash */
+    pub public_url: Arc<String>,
     pub admin_dir: Arc<String>,
     pub visitor_dir: Arc<String>
 }
@@ -27,50 +56,182 @@ pub enum StateInitError {
 
     #[error("Data base error: {0}")]
     DataBaseError(#[from] DbErr),
+
+    #[error("Failed to rehash legacy passwords: {0}")]
+    PasswordRehashError(#[from] password::PasswordError),
+
+    #[error("Failed to write the bootstrap invite token: {0}")]
+    BootstrapTokenError(#[from] io::Error),
+
+    #[error("PUBLIC_URL must be an absolute http(s) url without query, got {0}")]
+    InvalidPublicUrl(String),
+}
+
+/// `PUBLIC_URL`, used for links in mails and exports
+fn public_url() -> Result<String, StateInitError> {
+    let Ok(url) = env::var("PUBLIC_URL") else {
+        tracing::warn!("PUBLIC_URL not set, mails and exports will link to {DEV_PUBLIC_URL}");
+        return Ok(DEV_PUBLIC_URL.to_owned());
+    };
+
+    let host = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .unwrap_or_default();
+    if host.is_empty() || host.starts_with('/') || url.contains(['?', '#', ' ']) {
+        return Err(StateInitError::InvalidPublicUrl(url));
+    }
+
+    Ok(url.trim_end_matches('/').to_owned())
+}
+
+/// Only the server's user can read the token
+fn write_token_file(path: &Path, token: &str) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    /* `mode` only applies to new files */
+    file.set_permissions(Permissions::from_mode(0o600))?;
+    writeln!(file, "{token}")
 }
 
 impl AppState {
     pub async fn init(admin_dir: String, visitor_dir: String) -> Result<Self, StateInitError> {
+        let public_url = public_url()?;
         let db_conn = sea_orm::Database::connect(env::var("DATABASE_URL")?).await?;
+
+        /* rows created before passwords were hashed, or inserted by hand */
+        let rehashed = password::rehash_legacy(&db_conn).await?;
+        if rehashed > 0 {
+            tracing::warn!("Rehashed {rehashed} plaintext admin password(s)");
+        }
+
+        /* kept out of the logs, stdout included unless someone is watching it */
+        if let Some(token) = invite::bootstrap(&db_conn).await? {
+            let path = invite::bootstrap_token_file();
+            write_token_file(&path, &token)?;
+            tracing::warn!(
+                "No admins yet, register the first owner with the invite token in {}",
+                path.display()
+            );
+            if io::stdout().is_terminal() {
+                println!("Bootstrap invite token: {token}");
+            }
+        }
+
+        let keys = KeyStore::load(&db_conn, KeyPurpose::Admin).await?;
+        let visitor_keys = KeyStore::load(&db_conn, KeyPurpose::Visitor).await?;
+
         let s = Self {
             db_conn: db_conn.clone(),
+            keys: keys.clone(),
+            throttle: Throttle::from_env(&db_conn),
+            mailer: mailer::from_env(),
+            visitor_sessions: VisitorSessions::new(visitor_keys.clone()),
+            public_url: Arc::new(public_url),
             admin_dir: Arc::new(admin_dir),
             visitor_dir: Arc::new(visitor_dir),
         };
 
+        /* pick up keys rotated by other instances */
+        let keys_db_conn = db_conn.clone();
         tokio::spawn(async move {
             loop {
-                let now = chrono::Local::now().naive_local();
-                let res = visitor::Entity::delete_many()
-                    .filter(visitor::Column::TimeOut.lte(now.clone()))
-                    .exec(&db_conn)
-                    .await;
-
-                match res {
-                    Ok(r) => tracing::info!("Deleted {} timed out visitors", r.rows_affected),
-                    Err(e) => tracing::error!("DataBase Error: {}", e),
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                for keys in [&keys, &visitor_keys] {
+                    if let Err(e) = keys.reload(&keys_db_conn).await {
+                        tracing::error!("Failed to reload JWT keys: {e}");
+                    }
                 }
-
-                tokio::time::sleep(Duration::from_secs(30)).await;
             }
         });
 
         Ok(s)
     }
 
-    pub async fn validate_visitor(&self, uuid: &str) -> Result<bool, DbErr> {
-        tracing::warn!("got uuid here: {uuid}");
-        let count = visitor::Entity::find()
+    /// The visitor behind `uuid`, if the link exists and hasn't expired
+    pub async fn active_visitor(&self, uuid: &str) -> Result<Option<visitor::Model>, DbErr> {
+        visitor::Entity::find_by_id(uuid)
+            .filter(active_visitor_condition(chrono::Utc::now()))
+            .one(&self.db_conn)
+            .await
+    }
+
+    /// Admits a page load of `uuid` from `device`, counting it against the
+    /// link's limits and starting its validity window on first open.
+    /// Checks and counters are a single `UPDATE`, so concurrent opens of a
+    /// one-time link can't both get in. Returns the updated link if admitted.
+    pub async fn validate_visitor(
+        &self,
+        uuid: &str,
+        device: &str,
+    ) -> Result<Option<visitor::Model>, DbErr> {
+        let now = chrono::Utc::now();
+        let res = visitor::Entity::update_many()
+            .col_expr(
+                visitor::Column::PageLoads,
+                Expr::col(visitor::Column::PageLoads).add(1),
+            )
+            .col_expr(
+                visitor::Column::DeviceIds,
+                Expr::cust_with_values(
+                    r#"CASE WHEN $1 = ANY("device_ids") THEN "device_ids" ELSE array_append("device_ids", $1) END"#,
+                    [device],
+                ),
+            )
+            .col_expr(
+                visitor::Column::TimeOut,
+                Expr::cust_with_values(
+                    r#"CASE WHEN "activated_at" IS NULL AND "activate_for_sec" IS NOT NULL THEN $1 + "activate_for_sec" * INTERVAL '1 second' ELSE "time_out" END"#,
+                    [now],
+                ),
+            )
+            .col_expr(
+                visitor::Column::ActivatedAt,
+                Expr::cust_with_values(r#"COALESCE("activated_at", $1)"#, [now]),
+            )
             .filter(visitor::Column::Uuid.eq(uuid))
-            .filter(visitor::Column::TimeOut.gte(chrono::Local::now().naive_local()))
-            .count(&self.db_conn)
+            .filter(active_visitor_condition(now))
+            .filter(
+                Condition::any()
+                    .add(visitor::Column::MaxPageLoads.is_null())
+                    .add(Expr::col(visitor::Column::PageLoads).lt(Expr::col(visitor::Column::MaxPageLoads))),
+            )
+            .filter(
+                Condition::any()
+                    .add(visitor::Column::MaxDevices.is_null())
+                    .add(Expr::cust_with_values(r#"$1 = ANY("device_ids")"#, [device]))
+                    .add(Expr::cust(r#"cardinality("device_ids") < "max_devices""#)),
+            )
+            .exec_with_returning(&self.db_conn)
             .await?;
 
-        if count > 1 {
-            tracing::warn!("It's probaly an error, or there are 2 duplicate uuids");
-        }
-        
-        tracing::warn!("Count={count}");
-        Ok(count == 1)
+        Ok(res.into_iter().next())
+    }
+
+    /// Updates the visitor's last-seen time, counting a new visit when it
+    /// has been idle for longer than `VISIT_IDLE_GAP`
+    pub async fn record_visit(&self, uuid: &str) -> Result<(), DbErr> {
+        let now = chrono::Utc::now();
+        let new_visit = Expr::col(visitor::Column::LastSeenAt)
+            .is_null()
+            .or(Expr::col(visitor::Column::LastSeenAt).lt(now - VISIT_IDLE_GAP));
+
+        visitor::Entity::update_many()
+            .col_expr(
+                visitor::Column::VisitCount,
+                Expr::col(visitor::Column::VisitCount).add(
+                    Expr::case(new_visit, 1).finally(0),
+                ),
+            )
+            .col_expr(visitor::Column::LastSeenAt, Expr::value(now))
+            .filter(visitor::Column::Uuid.eq(uuid))
+            .exec(&self.db_conn)
+            .await?;
+
+        Ok(())
     }
 }
@@ -0,0 +1,75 @@
+use crate::{
+    entities::{sea_orm_active_enums::VisitorEventKind, visitor_event},
+    state::AppState,
+};
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, Request},
+    middleware::Next,
+    response::Response,
+};
+use sea_orm::{ActiveModelTrait, DatabaseConnection, Set};
+
+use super::VisitorClaims;
+
+/// Appends to the `visitor_event` log. Runs in the background and only logs
+/// failures, browsing must never break because of it.
+pub fn record(
+    db_conn: &DatabaseConnection,
+    uuid: String,
+    kind: VisitorEventKind,
+    path: String,
+    query: Option<String>,
+) {
+    let db_conn = db_conn.clone();
+    tokio::spawn(async move {
+        let event = visitor_event::ActiveModel {
+            id: sea_orm::NotSet,
+            visitor_uuid: Set(uuid),
+            kind: Set(kind),
+            path: Set(path),
+            query: Set(query),
+            created_at: Set(chrono::Utc::now()),
+        };
+
+        if let Err(e) = event.insert(&db_conn).await {
+            tracing::error!("Failed to record visitor event: {e}");
+        }
+    });
+}
+
+/* players fetch videos in many ranges, only the first one counts as a view */
+fn is_continuation(req: &Request<Body>) -> bool {
+    req.headers()
+        .get(header::RANGE)
+        .and_then(|range| range.to_str().ok())
+        .is_some_and(|range| !range.starts_with("bytes=0-"))
+}
+
+/// Logs successful project listings and media fetches, has to run inside
+/// `validate_visitor_cookie` which provides the `VisitorClaims`
+pub async fn log_activity(State(state): State<AppState>, req: Request<Body>, next: Next) -> Response {
+    let Some(claims) = req.extensions().get::<VisitorClaims>().cloned() else {
+        return next.run(req).await;
+    };
+
+    let path = req.uri().path().to_owned();
+    let query = req.uri().query().map(str::to_owned);
+    let kind = if path.starts_with("/projects/storage/") {
+        VisitorEventKind::Media
+    } else if path == "/projects" || path == "/projects/" {
+        /* years, countries, facets and exports are lookups, not browsing */
+        VisitorEventKind::ProjectList
+    } else {
+        return next.run(req).await;
+    };
+    let skip = is_continuation(&req);
+
+    let res = next.run(req).await;
+    if res.status().is_success() && !skip {
+        record(&state.db_conn, claims.sub, kind, path, query);
+    }
+
+    res
+}
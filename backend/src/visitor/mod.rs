@@ -0,0 +1,355 @@
+use std::{net::SocketAddr, path::PathBuf};
+
+use crate::{
+    admin::{password, throttle::Key},
+    common::{self, ProjectScope},
+    entities::{projects, sea_orm_active_enums::VisitorEventKind, visitor},
+    state::AppState,
+};
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Path, State},
+    http::{header, Request, StatusCode},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Response},
+    routing, Extension, Form, Json,
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use chrono::{DateTime, TimeDelta, Utc};
+use sea_orm::{EntityTrait, PaginatorTrait};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::{fs::File, io::AsyncReadExt};
+use tower_cookies::cookie::time;
+use tower_http::services::ServeDir;
+use uuid::Uuid;
+
+mod activity;
+mod session;
+mod sweeper;
+
+pub(crate) use session::MAX_SESSION_FOR;
+pub use session::{VisitorClaims, VisitorSessions};
+pub use sweeper::{Clock, SweepReport, Sweeper, SweeperConfig, SweeperHandle, SystemClock};
+
+/* signed session token, see `VisitorSessions` */
+const VISITOR_SESSION_COOKIE_NAME: &'static str = "visitor-session";
+/* identifies the browser, for links limited to a number of devices */
+const VISITOR_DEVICE_COOKIE_NAME: &'static str = "visitor-device";
+const DEVICE_COOKIE_FOR: TimeDelta = TimeDelta::days(365);
+
+async fn read_file(path: impl AsRef<std::path::Path>) -> Option<String> {
+    let mut file = File::open(path).await.ok()?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).await.ok()?;
+    Some(contents)
+}
+
+/// Served instead of the portfolio while a passcode is needed, posts back to
+/// the same url
+const PASSCODE_FORM: &'static str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Passcode required</title>
+<style>
+body { font-family: sans-serif; display: flex; justify-content: center; margin-top: 20vh; }
+form { display: flex; flex-direction: column; gap: 0.5rem; }
+.error { color: #b00020; }
+</style>
+</head>
+<body>
+<form method="post">
+<label for="passcode">Enter the passcode you received with this link</label>
+<input id="passcode" name="passcode" type="password" autocomplete="off" autofocus required>
+<p class="error">{error}</p>
+<button type="submit">Continue</button>
+</form>
+</body>
+</html>
+"#;
+
+fn passcode_form(status: StatusCode, error: &str) -> Response {
+    (status, Html::from(PASSCODE_FORM.replace("{error}", error))).into_response()
+}
+
+fn known_device(visitor: &visitor::Model, cookie_jar: &CookieJar) -> bool {
+    cookie_jar
+        .get(VISITOR_DEVICE_COOKIE_NAME)
+        .is_some_and(|device| visitor.device_ids.iter().any(|d| d == device.value()))
+}
+
+/* a device gets onto the list by opening the link, after the passcode if any,
+and drops off it when the passcode changes */
+fn device_allowed(visitor: &visitor::Model, claims: &VisitorClaims) -> bool {
+    (visitor.max_devices.is_none() && visitor.passcode_hash.is_none())
+        || visitor.device_ids.contains(&claims.dev)
+}
+
+fn cookie<'c>(name: &'c str, value: String, max_age: TimeDelta) -> Cookie<'c> {
+    Cookie::build((name, value))
+        .path("/")
+        .http_only(true)
+        .secure(true)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::seconds(max_age.num_seconds()))
+        .build()
+}
+
+/// Lets requests with a valid session through, the link itself is read
+/// from `VisitorSessions`' cache so revocations still apply
+pub async fn validate_visitor_cookie(
+    State(state): State<AppState>,
+    cookie_jar: CookieJar,
+    mut req: Request<Body>,
+    next: Next,
+) -> impl IntoResponse {
+    let Some(token) = cookie_jar.get(VISITOR_SESSION_COOKIE_NAME) else {
+        return (
+            StatusCode::EXPECTATION_FAILED,
+            Json(json!({"error": format!("No cookie with name={}", VISITOR_SESSION_COOKIE_NAME)})),
+        )
+            .into_response();
+    };
+
+    let Some(claims) = state.visitor_sessions.verify(&state.db_conn, token.value()).await else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    match state.visitor_sessions.visitor(&state.db_conn, &claims.sub).await {
+        Ok(Some(visitor)) if !device_allowed(&visitor, &claims) => {
+            tracing::warn!("Visitor {} used from an unknown device", visitor.uuid);
+            StatusCode::UNAUTHORIZED.into_response()
+        }
+        Ok(Some(visitor)) => {
+            if state.visitor_sessions.should_record_visit(&visitor.uuid) {
+                if let Err(e) = state.record_visit(&visitor.uuid).await {
+                    tracing::error!("Failed to record visit: {e}");
+                }
+            }
+            req.extensions_mut().insert(ProjectScope::from(&visitor));
+            req.extensions_mut().insert(claims);
+            next.run(req).await
+        }
+        /* revoked or expired since the session was issued */
+        Ok(None) => StatusCode::UNAUTHORIZED.into_response(),
+        Err(e) => {
+            tracing::error!("DataBase Error: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Counts the page load and hands out the portfolio with the cookies
+async fn admit(state: &AppState, jar: CookieJar, uuid: String) -> Response {
+    let device = jar
+        .get(VISITOR_DEVICE_COOKIE_NAME)
+        .map(|c| c.value().to_owned())
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    let visitor = match state.validate_visitor(&uuid, &device).await {
+        Ok(Some(visitor)) => visitor,
+        Ok(None) => return invalid_uuid(),
+        Err(e) => {
+            tracing::error!("DataBase Error: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    state.visitor_sessions.invalidate(&uuid);
+    activity::record(&state.db_conn, uuid, VisitorEventKind::PageOpen, "/home".into(), None);
+
+    let (token, max_age) = match state.visitor_sessions.issue(&visitor, &device) {
+        Ok(issued) => issued,
+        Err(e) => {
+            tracing::error!("Failed to issue visitor session: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let file =
+        match read_file(PathBuf::from(state.visitor_dir.as_ref()).join("index.html")).await {
+            None => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            Some(file) => file,
+        };
+    let jar = jar
+        .add(cookie(VISITOR_SESSION_COOKIE_NAME, token, max_age))
+        .add(cookie(VISITOR_DEVICE_COOKIE_NAME, device, DEVICE_COOKIE_FOR));
+    (StatusCode::OK, jar, Html::from(file)).into_response()
+}
+
+fn invalid_uuid() -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        Json(json!({"error": "Invalid visitor uuid"})),
+    )
+        .into_response()
+}
+
+pub async fn page(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Path(uuid): Path<String>,
+) -> impl IntoResponse {
+    tracing::warn!("Got uuid on login: {uuid}");
+
+    match state.active_visitor(&uuid).await {
+        Ok(Some(visitor)) if visitor.passcode_hash.is_some() && !known_device(&visitor, &jar) => {
+            passcode_form(StatusCode::OK, "")
+        }
+        Ok(Some(_)) => admit(&state, jar, uuid).await,
+        Ok(None) => invalid_uuid(),
+        Err(e) => {
+            tracing::error!("DataBase Error: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PasscodeForm {
+    passcode: String,
+}
+
+/// Checks the passcode posted by `PASSCODE_FORM`, throttled per IP and per
+/// link like admin logins
+pub async fn submit_passcode(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    jar: CookieJar,
+    Path(uuid): Path<String>,
+    Form(form): Form<PasscodeForm>,
+) -> impl IntoResponse {
+    let keys = [Key::Ip(addr.ip()), Key::Visitor(uuid.clone())];
+
+    match state.throttle.retry_after(&keys).await {
+        Ok(None) => {}
+        Ok(Some(wait)) => return too_many_attempts(wait),
+        Err(e) => {
+            tracing::error!("Failed to read passcode attempts: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
+    let visitor = match state.active_visitor(&uuid).await {
+        Ok(Some(visitor)) => visitor,
+        Ok(None) => return invalid_uuid(),
+        Err(e) => {
+            tracing::error!("DataBase Error: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let Some(hash) = visitor.passcode_hash else {
+        return admit(&state, jar, uuid).await;
+    };
+
+    match password::verify(form.passcode, Some(hash)).await {
+        Ok(true) => {
+            if let Err(e) = state.throttle.succeeded(&keys[1]).await {
+                tracing::error!("Failed to reset passcode attempts: {e}");
+            }
+            admit(&state, jar, uuid).await
+        }
+        Ok(false) => match state.throttle.failed(&keys).await {
+            Ok(Some(wait)) => too_many_attempts(wait),
+            Ok(None) => passcode_form(StatusCode::UNAUTHORIZED, "Wrong passcode"),
+            Err(e) => {
+                tracing::error!("Failed to record passcode attempt: {e}");
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        },
+        Err(e) => {
+            tracing::error!("Passcode check failed: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+fn too_many_attempts(wait: TimeDelta) -> Response {
+    /* round up, `Retry-After: 0` would invite an immediate retry */
+    let secs = (wait.num_milliseconds() + 999) / 1000;
+    let mut res = passcode_form(
+        StatusCode::TOO_MANY_REQUESTS,
+        &format!("Too many wrong passcodes, try again in {secs} seconds"),
+    );
+    res.headers_mut().insert(header::RETRY_AFTER, secs.into());
+    res
+}
+
+#[derive(Serialize)]
+pub struct DefaultFilter {
+    pub country: Option<String>,
+    pub year: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct SessionResponse {
+    pub client_name: Option<String>,
+    pub client_company: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    /* project to open right away, if the link may see it */
+    pub landing_project_id: Option<i32>,
+    pub default_filter: DefaultFilter,
+}
+
+/// What the visitor frontend needs to open on the work the link was made for
+pub async fn session(
+    State(state): State<AppState>,
+    Extension(claims): Extension<VisitorClaims>,
+) -> impl IntoResponse {
+    let visitor = match state.visitor_sessions.visitor(&state.db_conn, &claims.sub).await {
+        Ok(Some(visitor)) => visitor,
+        Ok(None) => return StatusCode::UNAUTHORIZED.into_response(),
+        Err(e) => {
+            tracing::error!("DataBase Error: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let landing_project_id = match visitor.landing_project_id {
+        Some(id) => {
+            let visible = ProjectScope::from(&visitor)
+                .apply(projects::Entity::find_by_id(id))
+                .count(&state.db_conn)
+                .await;
+
+            match visible {
+                Ok(n) => (n > 0).then_some(id),
+                Err(e) => {
+                    tracing::error!("DataBase Error: {e}");
+                    return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                }
+            }
+        }
+        None => None,
+    };
+
+    Json(SessionResponse {
+        client_name: visitor.client_name,
+        client_company: visitor.client_company,
+        expires_at: visitor.time_out,
+        landing_project_id,
+        default_filter: DefaultFilter {
+            country: visitor.default_country,
+            year: visitor.default_year,
+        },
+    })
+    .into_response()
+}
+
+pub fn static_router(state: AppState) -> axum::Router<AppState> {
+    axum::Router::new()
+        .nest_service("/", ServeDir::new(state.visitor_dir.as_ref()))
+}
+
+pub fn api_router(state: AppState) -> axum::Router<AppState> {
+    axum::Router::new()
+        .route("/session", routing::get(session))
+        .nest("/projects", common::get_router(state.clone()))
+        .merge(common::get_export_router())
+        .layer(middleware::from_fn_with_state(state.clone(), activity::log_activity))
+        .layer(middleware::from_fn_with_state(
+            state,
+            validate_visitor_cookie,
+        ))
+}
@@ -0,0 +1,164 @@
+use crate::{admin::KeyStore, entities::visitor, state::active_visitor_condition};
+use chrono::{TimeDelta, Utc};
+use jsonwebtoken::{decode, decode_header, encode, Header, Validation};
+use sea_orm::{DatabaseConnection, DbErr, EntityTrait, QueryFilter};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/* revoked or edited links are picked up at most this late */
+const CACHE_FOR: Duration = Duration::from_secs(30);
+/* `last_seen_at` doesn't need to be more precise than this */
+const RECORD_VISIT_EVERY: Duration = Duration::from_secs(60);
+/* cache drops stale entries once it grows past this */
+const CACHE_PRUNE_AT: usize = 10_000;
+/* links that never expire still get a session that does */
+pub(crate) const MAX_SESSION_FOR: TimeDelta = TimeDelta::days(30);
+/* checked on top of the separate keys, admin tokens never pass as sessions */
+const AUDIENCE: &str = "visitor";
+
+/// Carried in the `visitor-session` cookie, proves the browser opened the
+/// link (and passed its passcode) without asking the database
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct VisitorClaims {
+    /* uuid of the `visitor` row */
+    pub sub: String,
+    /* device id the link was opened with */
+    pub dev: String,
+    pub exp: usize,
+    pub aud: String,
+}
+
+struct Cached {
+    visitor: Option<visitor::Model>,
+    fetched_at: Instant,
+    visit_recorded_at: Option<Instant>,
+}
+
+/// Issues and checks visitor session tokens, and caches the links they
+/// belong to so most requests don't touch the database.
+/// Tokens are signed with the visitor `KeyStore`, shared through the database
+/// so they survive restarts and work on every instance. Rotating admin keys
+/// leaves them alone, as used up one-time links couldn't be reopened.
+#[derive(Clone)]
+pub struct VisitorSessions {
+    keys: KeyStore,
+    cache: Arc<Mutex<HashMap<String, Cached>>>,
+}
+
+impl VisitorSessions {
+    pub fn new(keys: KeyStore) -> Self {
+        Self {
+            keys,
+            cache: Default::default(),
+        }
+    }
+
+    /// Session token for `visitor` opened from `device`, with how long the
+    /// cookie should live, ending with the link itself when it expires
+    pub fn issue(
+        &self,
+        visitor: &visitor::Model,
+        device: &str,
+    ) -> Result<(String, TimeDelta), jsonwebtoken::errors::Error> {
+        let now = Utc::now();
+        let valid_for = visitor
+            .time_out
+            .map_or(MAX_SESSION_FOR, |time_out| (time_out - now).min(MAX_SESSION_FOR))
+            .max(TimeDelta::zero());
+
+        let (kid, key) = self.keys.encoding_key();
+        let token = encode(
+            &Header { kid: Some(kid), ..Default::default() },
+            &VisitorClaims {
+                sub: visitor.uuid.clone(),
+                dev: device.to_owned(),
+                exp: (Utc::now() + valid_for).timestamp() as usize,
+                aud: AUDIENCE.to_owned(),
+            },
+            &key,
+        )?;
+
+        Ok((token, valid_for))
+    }
+
+    pub async fn verify(&self, db_conn: &DatabaseConnection, token: &str) -> Option<VisitorClaims> {
+        let kid = decode_header(token).ok()?.kid?;
+        let key = self.keys.decoding_key(db_conn, &kid).await?;
+
+        let mut validation = Validation::default();
+        validation.set_audience(&[AUDIENCE]);
+
+        decode::<VisitorClaims>(token, &key, &validation)
+            .ok()
+            .map(|data| data.claims)
+    }
+
+    /// The link behind `uuid` if it is still active, read through the cache
+    pub async fn visitor(
+        &self,
+        db_conn: &DatabaseConnection,
+        uuid: &str,
+    ) -> Result<Option<visitor::Model>, DbErr> {
+        let now = Utc::now();
+
+        if let Some(cached) = self.cache.lock().unwrap().get(uuid) {
+            if cached.fetched_at.elapsed() < CACHE_FOR {
+                /* may have run out since it was cached */
+                return Ok(cached
+                    .visitor
+                    .clone()
+                    .filter(|v| v.time_out.map_or(true, |time_out| time_out >= now)));
+            }
+        }
+
+        let visitor = visitor::Entity::find_by_id(uuid)
+            .filter(active_visitor_condition(now))
+            .one(db_conn)
+            .await?;
+
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= CACHE_PRUNE_AT {
+            cache.retain(|_, c| c.fetched_at.elapsed() < CACHE_FOR);
+        }
+
+        let visit_recorded_at = cache.get(uuid).and_then(|c| c.visit_recorded_at);
+        cache.insert(
+            uuid.to_owned(),
+            Cached {
+                visitor: visitor.clone(),
+                fetched_at: Instant::now(),
+                visit_recorded_at,
+            },
+        );
+
+        Ok(visitor)
+    }
+
+    /// Whether the visit of `uuid` is due to be written to the database,
+    /// marking it as written if so
+    pub fn should_record_visit(&self, uuid: &str) -> bool {
+        let mut cache = self.cache.lock().unwrap();
+        let Some(cached) = cache.get_mut(uuid) else {
+            return true;
+        };
+
+        let due = cached
+            .visit_recorded_at
+            .map_or(true, |at| at.elapsed() >= RECORD_VISIT_EVERY);
+        if due {
+            cached.visit_recorded_at = Some(Instant::now());
+        }
+
+        due
+    }
+
+    /// Makes this instance re-read the link on its next request, other
+    /// instances catch up within `CACHE_FOR`
+    pub fn invalidate(&self, uuid: &str) {
+        self.cache.lock().unwrap().remove(uuid);
+    }
+}
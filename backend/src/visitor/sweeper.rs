@@ -0,0 +1,316 @@
+use crate::entities::visitor;
+use chrono::{DateTime, TimeDelta, Utc};
+use sea_orm::{
+    sea_query::Expr, ColumnTrait, DatabaseConnection, DbErr, DeleteMany, EntityTrait, QueryFilter,
+    UpdateMany,
+};
+use std::{env, sync::Arc, time::Duration};
+use tokio::{sync::watch, task::JoinHandle};
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(30);
+const DEFAULT_RETENTION: TimeDelta = TimeDelta::days(90);
+
+/// Where the sweeper gets the time from, swapped out to test it
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SweeperConfig {
+    /* time between two sweeps */
+    pub interval: Duration,
+    /* how long expired links are kept for reporting before they are purged */
+    pub retention: TimeDelta,
+}
+
+impl Default for SweeperConfig {
+    fn default() -> Self {
+        Self {
+            interval: DEFAULT_INTERVAL,
+            retention: DEFAULT_RETENTION,
+        }
+    }
+}
+
+impl SweeperConfig {
+    /// `VISITOR_SWEEP_INTERVAL_SEC` and `VISITOR_RETENTION_DAYS`, defaults
+    /// for anything unset or unparsable
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let read = |name: &str| env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+
+        Self {
+            interval: read("VISITOR_SWEEP_INTERVAL_SEC")
+                .map_or(default.interval, Duration::from_secs),
+            retention: read("VISITOR_RETENTION_DAYS").map_or(default.retention, |days| {
+                retention_days(days).unwrap_or_else(|| {
+                    tracing::warn!("VISITOR_RETENTION_DAYS={days} is out of range, using the default");
+                    default.retention
+                })
+            }),
+        }
+    }
+}
+
+/// `days` as a retention period, if `now - retention` can't overflow.
+/// Checked against the epoch, which every sweep's `now` comes after
+fn retention_days(days: u64) -> Option<TimeDelta> {
+    i64::try_from(days)
+        .ok()
+        .and_then(TimeDelta::try_days)
+        .filter(|retention| DateTime::UNIX_EPOCH.checked_sub_signed(*retention).is_some())
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SweepReport {
+    /* links that ran out and were soft-deleted */
+    pub expired: u64,
+    /* soft-deleted links dropped after the retention period */
+    pub purged: u64,
+}
+
+/// Soft-deletes expired visitor links and later purges them, together
+/// with their activity log
+pub struct Sweeper {
+    db_conn: DatabaseConnection,
+    config: SweeperConfig,
+    clock: Arc<dyn Clock>,
+}
+
+impl Sweeper {
+    pub fn new(db_conn: DatabaseConnection, config: SweeperConfig, clock: Arc<dyn Clock>) -> Self {
+        Self { db_conn, config, clock }
+    }
+
+    pub fn from_env(db_conn: DatabaseConnection) -> Self {
+        Self::new(db_conn, SweeperConfig::from_env(), Arc::new(SystemClock))
+    }
+
+    fn expire(now: DateTime<Utc>) -> UpdateMany<visitor::Entity> {
+        visitor::Entity::update_many()
+            .col_expr(visitor::Column::DeletedAt, Expr::value(now))
+            .filter(visitor::Column::DeletedAt.is_null())
+            .filter(visitor::Column::TimeOut.lt(now))
+    }
+
+    fn purge(&self, now: DateTime<Utc>) -> DeleteMany<visitor::Entity> {
+        visitor::Entity::delete_many()
+            .filter(visitor::Column::DeletedAt.lte(now - self.config.retention))
+    }
+
+    pub async fn sweep_once(&self) -> Result<SweepReport, DbErr> {
+        let now = self.clock.now();
+
+        let expired = Self::expire(now).exec(&self.db_conn).await?.rows_affected;
+        let purged = self.purge(now).exec(&self.db_conn).await?.rows_affected;
+
+        Ok(SweepReport { expired, purged })
+    }
+
+    /// Sweeps every `interval` until stopped through the returned handle
+    pub fn spawn(self) -> SweeperHandle {
+        let (stop, mut stopped) = watch::channel(false);
+
+        let task = tokio::spawn(async move {
+            loop {
+                match self.sweep_once().await {
+                    Ok(SweepReport { expired: 0, purged: 0 }) => {}
+                    Ok(r) => tracing::info!(
+                        "Soft-deleted {} expired visitors, purged {}",
+                        r.expired,
+                        r.purged
+                    ),
+                    Err(e) => tracing::error!("DataBase Error: {}", e),
+                }
+
+                tokio::select! {
+                    _ = tokio::time::sleep(self.config.interval) => {}
+                    _ = stopped.changed() => break,
+                }
+            }
+        });
+
+        SweeperHandle { stop, task }
+    }
+}
+
+pub struct SweeperHandle {
+    stop: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl SweeperHandle {
+    /// Waits for a sweep in progress to finish, then ends the task
+    pub async fn stop(self) {
+        let _ = self.stop.send(true);
+        if let Err(e) = self.task.await {
+            tracing::error!("Visitor sweeper failed: {e}");
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use sea_orm::{
+        ActiveModelTrait, ConnectOptions, ConnectionTrait, Database, DbBackend, QueryTrait,
+        Schema, Set,
+    };
+
+    struct FixedClock(DateTime<Utc>);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> DateTime<Utc> {
+            self.0
+        }
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 20, 12, 0, 0).unwrap()
+    }
+
+    fn sweeper(db_conn: DatabaseConnection, now: DateTime<Utc>) -> Sweeper {
+        let config = SweeperConfig {
+            interval: Duration::from_secs(1),
+            retention: TimeDelta::days(90),
+        };
+        Sweeper::new(db_conn, config, Arc::new(FixedClock(now)))
+    }
+
+    #[test]
+    fn retention_days_rejects_overflowing_values() {
+        assert_eq!(retention_days(90), Some(TimeDelta::days(90)));
+        assert_eq!(retention_days(0), Some(TimeDelta::zero()));
+        assert_eq!(retention_days(u64::MAX), None);
+        assert_eq!(retention_days(i64::MAX as u64), None);
+        assert_eq!(retention_days(1_000_000_000), None);
+    }
+
+    #[test]
+    fn queries_use_the_clock() {
+        let sweeper = sweeper(DatabaseConnection::Disconnected, now());
+
+        let expire = Sweeper::expire(now()).build(DbBackend::Postgres);
+        assert_eq!(
+            expire.to_string(),
+            r#"UPDATE "visitor" SET "deleted_at" = '2024-06-20 12:00:00 +00:00' WHERE "visitor"."deleted_at" IS NULL AND "visitor"."time_out" < '2024-06-20 12:00:00 +00:00'"#
+        );
+
+        /* 90 days of retention */
+        let purge = sweeper.purge(now()).build(DbBackend::Postgres);
+        assert_eq!(
+            purge.to_string(),
+            r#"DELETE FROM "visitor" WHERE "visitor"."deleted_at" <= '2024-03-22 12:00:00 +00:00'"#
+        );
+    }
+
+    fn visitor(
+        uuid: &str,
+        time_out: Option<DateTime<Utc>>,
+        deleted_at: Option<DateTime<Utc>>,
+    ) -> visitor::ActiveModel {
+        visitor::ActiveModel {
+            uuid: Set(uuid.to_owned()),
+            time_out: Set(time_out),
+            label: Set(None),
+            client_name: Set(None),
+            client_company: Set(None),
+            notes: Set(None),
+            created_by: Set(None),
+            created_at: Set(now() - TimeDelta::days(365)),
+            last_seen_at: Set(None),
+            visit_count: Set(0),
+            scope_project_ids: Set(None),
+            scope_country: Set(None),
+            scope_year: Set(None),
+            max_page_loads: Set(None),
+            page_loads: Set(0),
+            max_devices: Set(None),
+            device_ids: Set(vec![]),
+            activate_for_sec: Set(None),
+            activated_at: Set(None),
+            passcode_hash: Set(None),
+            deleted_at: Set(deleted_at),
+            landing_project_id: Set(None),
+            default_country: Set(None),
+            default_year: Set(None),
+        }
+    }
+
+    /// Runs against `TEST_DATABASE_URL` in a throwaway schema, skipped
+    /// when it isn't set
+    #[tokio::test]
+    async fn sweep_once_expires_then_purges() {
+        let Ok(url) = env::var("TEST_DATABASE_URL") else {
+            eprintln!("TEST_DATABASE_URL not set, skipping");
+            return;
+        };
+
+        /* one connection, so `search_path` holds for every query */
+        let db_conn = Database::connect(ConnectOptions::new(url).max_connections(1).to_owned())
+            .await
+            .unwrap();
+        let schema = format!("sweeper_test_{}", uuid::Uuid::new_v4().simple());
+        db_conn
+            .execute_unprepared(&format!(r#"CREATE SCHEMA "{schema}"; SET search_path TO "{schema}""#))
+            .await
+            .unwrap();
+        let backend = db_conn.get_database_backend();
+        db_conn
+            .execute(backend.build(&Schema::new(backend).create_table_from_entity(visitor::Entity)))
+            .await
+            .unwrap();
+
+        let day = TimeDelta::days(1);
+        for row in [
+            visitor("active", Some(now() + day), None),
+            visitor("never-expires", None, None),
+            visitor("expired", Some(now() - day), None),
+            visitor("deleted-recently", Some(now() - day * 10), Some(now() - day * 10)),
+            visitor("deleted-long-ago", Some(now() - day * 100), Some(now() - day * 91)),
+        ] {
+            row.insert(&db_conn).await.unwrap();
+        }
+
+        let report = sweeper(db_conn.clone(), now()).sweep_once().await.unwrap();
+        assert_eq!(report, SweepReport { expired: 1, purged: 1 });
+
+        let expired = visitor::Entity::find_by_id("expired").one(&db_conn).await.unwrap().unwrap();
+        assert_eq!(expired.deleted_at, Some(now()));
+        assert!(visitor::Entity::find_by_id("deleted-long-ago").one(&db_conn).await.unwrap().is_none());
+
+        /* nothing changes until the next link runs out */
+        let report = sweeper(db_conn.clone(), now()).sweep_once().await.unwrap();
+        assert_eq!(report, SweepReport::default());
+
+        /* 80 days on the recently deleted link passes retention, `active` expires */
+        let later = now() + day * 80;
+        let report = sweeper(db_conn.clone(), later).sweep_once().await.unwrap();
+        assert_eq!(report, SweepReport { expired: 1, purged: 1 });
+
+        let left: Vec<String> = visitor::Entity::find()
+            .all(&db_conn)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|v| v.uuid)
+            .collect();
+        assert_eq!(left.len(), 3);
+        assert!(!left.contains(&"deleted-recently".to_owned()));
+
+        db_conn
+            .execute_unprepared(&format!(r#"DROP SCHEMA "{schema}" CASCADE"#))
+            .await
+            .unwrap();
+    }
+}